@@ -0,0 +1,402 @@
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::thread;
+use std::time::Duration;
+
+use rand::{Rng, SeedableRng};
+use serde::Serialize;
+
+use tetris_rust::bot::Weights;
+use tetris_rust::figure::FigureKind;
+use tetris_rust::game::GameRng;
+use tetris_rust::gym::{Action, ActionSpace, Environment};
+use tetris_rust::mode::GameMode;
+
+const USAGE: &str = "\
+использование: tetris-sim [ПАРАМЕТРЫ]
+  --games N          сколько игр сыграть (по умолчанию 1000)
+  --seed N           seed первой игры, дальше подряд (по умолчанию 0)
+  --threads N        потоков (по умолчанию по числу ядер)
+  --mode РЕЖИМ       режим по ключу таблицы рекордов: Marathon, Sprint40,
+                     Ultra120, Dig10, Survival, Zen, Big, Cascade, Finesse100...
+                     (по умолчанию Marathon)
+  --bot БОТ          heuristic — встроенный бот, random — случайные постановки
+                     (по умолчанию heuristic)
+  --max-pieces N     прервать игру после N фигур (по умолчанию 1000)
+  --piece-time МС    игрового времени на фигуру, для режимов на время
+                     и подъёма мусора (по умолчанию 500)
+  --format ФОРМАТ    json или csv (по умолчанию json)
+  --help             показать эту справку";
+
+/// Поток генератора случайного бота; фигуры берутся из нулевого потока того же seed'а
+const BOT_RNG_STREAM: u64 = 1;
+
+/// Все виды фигур, в порядке вывода распределения
+const FIGURE_KINDS: [FigureKind; 7] = [
+	FigureKind::I, FigureKind::J, FigureKind::L, FigureKind::T,
+	FigureKind::S, FigureKind::Z, FigureKind::O,
+];
+
+#[derive(Clone, Copy, PartialEq)]
+enum BotKind {
+	Heuristic,
+	Random,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Format {
+	Json,
+	Csv,
+}
+
+/// Параметры командной строки
+struct Options {
+	games: u64,
+	first_seed: u64,
+	threads: usize,
+	mode: GameMode,
+	bot: BotKind,
+	max_pieces: u32,
+	piece_time: Duration,
+	format: Format,
+	/// Показать справку вместо симуляции
+	help: bool,
+}
+
+impl Default for Options {
+	fn default() -> Self {
+		Self {
+			games: 1000,
+			first_seed: 0,
+			threads: thread::available_parallelism().map_or(1, |threads| threads.get()),
+			mode: GameMode::Marathon,
+			bot: BotKind::Heuristic,
+			max_pieces: 1000,
+			piece_time: Duration::from_millis(500),
+			format: Format::Json,
+			help: false,
+		}
+	}
+}
+
+fn parse_number<T: std::str::FromStr>(flag: &str, value: &str) -> Result<T, String> {
+	value.parse().map_err(|_| format!("{flag}: не число: {value}"))
+}
+
+/// Режим по ключу таблицы рекордов, без учёта регистра
+fn parse_mode(value: &str) -> Result<GameMode, String> {
+	GameMode::defaults().into_iter()
+		.chain(GameMode::all_variants())
+		.find(|mode| mode.record_key().eq_ignore_ascii_case(value))
+		.ok_or_else(|| format!("--mode: неизвестный режим: {value}"))
+}
+
+fn parse_options(args: impl IntoIterator<Item = String>) -> Result<Options, String> {
+	let mut options = Options::default();
+	let mut args = args.into_iter();
+	while let Some(arg) = args.next() {
+		if matches!(arg.as_str(), "--help" | "-h") {
+			options.help = true;
+			continue;
+		}
+		let value = args.next().ok_or_else(|| format!("{arg}: нужно значение"))?;
+		match arg.as_str() {
+			"--games" => options.games = parse_number(&arg, &value)?,
+			"--seed" => options.first_seed = parse_number(&arg, &value)?,
+			"--threads" => options.threads = parse_number::<usize>(&arg, &value)?.max(1),
+			"--mode" => options.mode = parse_mode(&value)?,
+			"--bot" => options.bot = match value.as_str() {
+				"heuristic" => BotKind::Heuristic,
+				"random" => BotKind::Random,
+				_ => return Err(format!("--bot: неизвестный бот: {value}")),
+			},
+			"--max-pieces" => options.max_pieces = parse_number(&arg, &value)?,
+			"--piece-time" => options.piece_time = Duration::from_millis(parse_number(&arg, &value)?),
+			"--format" => options.format = match value.as_str() {
+				"json" => Format::Json,
+				"csv" => Format::Csv,
+				_ => return Err(format!("--format: неизвестный формат: {value}")),
+			},
+			_ => return Err(format!("неизвестный параметр: {arg}")),
+		}
+	}
+	if matches!(options.mode, GameMode::Puzzle | GameMode::Versus) {
+		return Err(String::from("--mode: головоломки и игру вдвоём симулировать нельзя"));
+	}
+	Ok(options)
+}
+
+/// Итог одной сыгранной игры
+struct GameSummary {
+	score: u32,
+	lines: u16,
+	pieces: u32,
+	/// Цель режима достигнута
+	completed: bool,
+	/// Игра прервана по `--max-pieces`
+	capped: bool,
+	/// Сколько фигур каждого вида поставлено, в порядке `FIGURE_KINDS`
+	pieces_by_kind: [u32; FIGURE_KINDS.len()],
+}
+
+fn play_game(options: &Options, seed: u64) -> GameSummary {
+	let weights = Weights::default();
+	// Тот же seed, но другой поток: выбор бота не повторяет очередь фигур
+	let mut rng = GameRng::seed_from_u64(seed);
+	rng.set_stream(BOT_RNG_STREAM);
+	let mut environment = Environment::new(ActionSpace::Placement)
+		.with_mode(options.mode)
+		.with_step_time(options.piece_time);
+	let mut observation = environment.reset(seed);
+	let mut pieces_by_kind = [0; FIGURE_KINDS.len()];
+
+	let mut done = environment.game().is_game_over();
+	while !done && environment.game().pieces_placed() < options.max_pieces {
+		let game = environment.game();
+		let placements = environment.legal_placements();
+		let index = match options.bot {
+			_ if placements.is_empty() => 0,
+			BotKind::Heuristic => (0..placements.len())
+				.map(|index| (weights.evaluate_placement(game.board(), &placements[index]), index))
+				.max_by(|(left, _), (right, _)| left.total_cmp(right))
+				.map_or(0, |(_, index)| index),
+			BotKind::Random => rng.random_range(0..placements.len()),
+		};
+
+		let kind = observation.current;
+		let pieces_before = game.pieces_placed();
+		let step = environment.step(Action::Placement(index));
+		// Постановок нет — фигуре некуда деться, дальше игра не пойдёт
+		if step.3.invalid_action {
+			break;
+		}
+		if step.3.pieces_placed != pieces_before {
+			pieces_by_kind[FIGURE_KINDS.iter().position(|known| *known == kind).unwrap()] += 1;
+		}
+		observation = step.0;
+		done = step.2;
+	}
+
+	let result = environment.game().result();
+	GameSummary {
+		score: result.score,
+		lines: result.lines,
+		pieces: environment.game().pieces_placed(),
+		completed: result.completed,
+		capped: !done && environment.game().pieces_placed() >= options.max_pieces,
+		pieces_by_kind,
+	}
+}
+
+/// Играет все игры, раздавая seed'ы потокам по одному
+fn play_games(options: &Options) -> Vec<GameSummary> {
+	let next_game = AtomicU64::new(0);
+	let mut summaries: Vec<(u64, GameSummary)> = thread::scope(|scope| {
+		let workers: Vec<_> = (0..options.threads)
+			.map(|_| scope.spawn(|| {
+				let mut summaries = Vec::new();
+				loop {
+					let game = next_game.fetch_add(1, Ordering::Relaxed);
+					if game >= options.games {
+						break summaries;
+					}
+					let seed = options.first_seed.wrapping_add(game);
+					summaries.push((seed, play_game(options, seed)));
+				}
+			}))
+			.collect();
+		workers.into_iter()
+			.flat_map(|worker| worker.join().unwrap())
+			.collect()
+	});
+	summaries.sort_by_key(|(seed, _)| *seed);
+	summaries.into_iter().map(|(_, summary)| summary).collect()
+}
+
+/// Среднее, медиана, минимум и максимум
+#[derive(Serialize)]
+struct Distribution {
+	mean: f64,
+	median: f64,
+	min: f64,
+	max: f64,
+}
+
+impl Distribution {
+	fn of(values: impl Iterator<Item = f64>) -> Self {
+		let mut values: Vec<f64> = values.collect();
+		if values.is_empty() {
+			return Self { mean: 0.0, median: 0.0, min: 0.0, max: 0.0 };
+		}
+		values.sort_by(f64::total_cmp);
+
+		let middle = values.len() / 2;
+		let median = match values.len() % 2 {
+			0 => (values[middle - 1] + values[middle]) / 2.0,
+			_ => values[middle],
+		};
+		Self {
+			mean: values.iter().sum::<f64>() / values.len() as f64,
+			median,
+			min: values[0],
+			max: values[values.len() - 1],
+		}
+	}
+}
+
+/// Итоговая статистика по всем играм
+#[derive(Serialize)]
+struct Statistics {
+	mode: String,
+	bot: &'static str,
+	games: usize,
+	first_seed: u64,
+	max_pieces: u32,
+	/// Игр, в которых достигнута цель режима
+	completed: usize,
+	/// Игр, прерванных по `--max-pieces`
+	capped: usize,
+	score: Distribution,
+	lines: Distribution,
+	/// Длина игры в фигурах
+	pieces: Distribution,
+	/// Сколько фигур каждого вида поставлено во всех играх
+	piece_distribution: BTreeMap<String, u64>,
+}
+
+impl Statistics {
+	fn aggregate(options: &Options, summaries: &[GameSummary]) -> Self {
+		Self {
+			mode: options.mode.record_key(),
+			bot: match options.bot {
+				BotKind::Heuristic => "heuristic",
+				BotKind::Random => "random",
+			},
+			games: summaries.len(),
+			first_seed: options.first_seed,
+			max_pieces: options.max_pieces,
+			completed: summaries.iter().filter(|summary| summary.completed).count(),
+			capped: summaries.iter().filter(|summary| summary.capped).count(),
+			score: Distribution::of(summaries.iter().map(|summary| summary.score as f64)),
+			lines: Distribution::of(summaries.iter().map(|summary| summary.lines as f64)),
+			pieces: Distribution::of(summaries.iter().map(|summary| summary.pieces as f64)),
+			piece_distribution: FIGURE_KINDS.iter()
+				.enumerate()
+				.map(|(index, kind)| (
+					format!("{kind:?}"),
+					summaries.iter().map(|summary| summary.pieces_by_kind[index] as u64).sum(),
+				))
+				.collect(),
+		}
+	}
+
+	/// Та же статистика плоскими строками «имя,значение»
+	fn to_csv(&self) -> String {
+		let mut rows = vec![
+			String::from("statistic,value"),
+			format!("mode,{}", self.mode),
+			format!("bot,{}", self.bot),
+			format!("games,{}", self.games),
+			format!("first_seed,{}", self.first_seed),
+			format!("max_pieces,{}", self.max_pieces),
+			format!("completed,{}", self.completed),
+			format!("capped,{}", self.capped),
+		];
+		for (metric, distribution) in [("score", &self.score), ("lines", &self.lines), ("pieces", &self.pieces)] {
+			rows.push(format!("{metric}_mean,{}", distribution.mean));
+			rows.push(format!("{metric}_median,{}", distribution.median));
+			rows.push(format!("{metric}_min,{}", distribution.min));
+			rows.push(format!("{metric}_max,{}", distribution.max));
+		}
+		for (kind, count) in &self.piece_distribution {
+			rows.push(format!("pieces_{kind},{count}"));
+		}
+		rows.join("\n")
+	}
+}
+
+fn main() {
+	let options = match parse_options(std::env::args().skip(1)) {
+		Ok(options) => options,
+		Err(message) => {
+			eprintln!("{message}\n{USAGE}");
+			std::process::exit(2);
+		}
+	};
+	if options.help {
+		println!("{USAGE}");
+		return;
+	}
+
+	let statistics = Statistics::aggregate(&options, &play_games(&options));
+	match options.format {
+		Format::Json => println!("{}", serde_json::to_string_pretty(&statistics).unwrap()),
+		Format::Csv => println!("{}", statistics.to_csv()),
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn parse(args: &[&str]) -> Result<Options, String> {
+		parse_options(args.iter().map(|arg| arg.to_string()))
+	}
+
+	fn summary(score: u32, pieces_by_kind: [u32; FIGURE_KINDS.len()]) -> GameSummary {
+		GameSummary {
+			score,
+			lines: (score / 100) as u16,
+			pieces: pieces_by_kind.iter().sum(),
+			completed: score > 0,
+			capped: false,
+			pieces_by_kind,
+		}
+	}
+
+	#[test]
+	fn parses_options() {
+		assert!(parse(&["--help"]).unwrap().help);
+		assert!(parse(&["--games", "5", "-h"]).unwrap().help);
+
+		let options = parse(&["--games", "5", "--bot", "random", "--format", "csv"]).unwrap();
+		assert_eq!(options.games, 5);
+		assert!(options.bot == BotKind::Random && options.format == Format::Csv && !options.help);
+
+		assert_eq!(parse(&["--games"]).err().unwrap(), "--games: нужно значение");
+		assert_eq!(parse(&["--games", "много"]).err().unwrap(), "--games: не число: много");
+	}
+
+	#[test]
+	fn distribution_summary() {
+		let odd = Distribution::of([3.0, 1.0, 2.0].into_iter());
+		assert_eq!((odd.mean, odd.median, odd.min, odd.max), (2.0, 2.0, 1.0, 3.0));
+		let even = Distribution::of([10.0, 1.0, 3.0, 2.0].into_iter());
+		assert_eq!((even.mean, even.median, even.min, even.max), (4.0, 2.5, 1.0, 10.0));
+		let empty = Distribution::of(std::iter::empty());
+		assert_eq!((empty.mean, empty.median, empty.min, empty.max), (0.0, 0.0, 0.0, 0.0));
+	}
+
+	#[test]
+	fn writes_csv_and_json() {
+		let options = parse(&["--seed", "7"]).unwrap();
+		let summaries = [summary(0, [1, 0, 0, 0, 0, 0, 1]), summary(400, [2, 1, 1, 1, 1, 1, 1])];
+		let statistics = Statistics::aggregate(&options, &summaries);
+
+		let csv = statistics.to_csv();
+		let rows: Vec<&str> = csv.lines().collect();
+		assert_eq!(rows[..4], ["statistic,value", "mode,Marathon", "bot,heuristic", "games,2"]);
+		assert!(rows.contains(&"first_seed,7"));
+		assert!(rows.contains(&"completed,1"));
+		assert!(rows.contains(&"score_mean,200"));
+		assert!(rows.contains(&"pieces_max,8"));
+		assert!(rows.contains(&"pieces_I,3"));
+		assert!(rows.contains(&"pieces_O,2"));
+
+		let json = serde_json::to_value(&statistics).unwrap();
+		assert_eq!(json["games"], 2);
+		assert_eq!(json["score"]["median"], 200.0);
+		assert_eq!(json["lines"]["max"], 4.0);
+		assert_eq!(json["piece_distribution"]["T"], 1);
+	}
+}
@@ -0,0 +1,488 @@
+use std::fmt;
+use std::iter;
+use std::time::Duration;
+
+use bitvec::prelude::*;
+use rand::{Rng, seq::index};
+use serde::{Serialize, Deserialize};
+
+use crate::shared::*;
+use crate::figure::Figure;
+
+/// Символ занятой клетки в текстовом виде доски
+pub const ASCII_FILLED_CELL: char = '#';
+/// Символ пустой клетки в текстовом виде доски
+pub const ASCII_EMPTY_CELL: char = '.';
+
+/// Ошибка разбора текстового вида доски. Строка и столбец считаются с 1.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BoardParseError {
+	pub line: usize,
+	pub column: usize,
+	pub message: String,
+}
+
+impl BoardParseError {
+	fn new(line: usize, column: usize, message: String) -> Self {
+		Self { line, column, message }
+	}
+}
+
+impl fmt::Display for BoardParseError {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		write!(f, "строка {}, столбец {}: {}", self.line, self.column, self.message)
+	}
+}
+
+impl std::error::Error for BoardParseError {}
+
+/// Как ведут себя клетки над убранными линиями
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum ClearGravity {
+	/// Строки над убранными линиями опускаются целиком, висящие клетки остаются висеть
+	#[default]
+	Naive,
+	/// Связные куски клеток падают независимо, пока не лягут; это может убрать
+	/// новые линии (цепочка)
+	Cascade,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Board {
+	pub size: Size,
+	pub cells: BitVec,
+	/// Игровое время, когда клетка была занята (для затухающего стакана)
+	lock_times: Vec<Duration>,
+	/// Сколько нижних строк — мусор, добавленный через `push_garbage_line`.
+	/// Мусор всегда лежит снизу, поэтому достаточно количества.
+	#[serde(default)]
+	garbage_height: usize,
+	/// Сколько клеток доски по каждой оси занимает одна клетка фигуры (2 — «большой» режим).
+	/// Позиции фигур задаются в логических клетках, размер доски — в настоящих.
+	#[serde(default = "default_cell_scale")]
+	cell_scale: usize,
+	#[serde(default)]
+	clear_gravity: ClearGravity,
+}
+
+fn default_cell_scale() -> usize {
+	1
+}
+
+impl Board {
+	pub fn new(size: Size) -> Self {
+		let cells = BitVec::from_iter(
+			iter::repeat_n(false, size.area())
+		);
+
+		let lock_times = vec![Duration::ZERO; size.area()];
+
+		Self { size, cells, lock_times, garbage_height: 0, cell_scale: default_cell_scale(), clear_gravity: ClearGravity::Naive }
+	}
+
+	/// Доска из текста: строки сверху вниз, `#` — занятая клетка, `.` — пустая.
+	/// Размер доски определяется текстом, все строки должны быть одной длины.
+	pub fn from_ascii(text: &str) -> Result<Self, BoardParseError> {
+		let lines: Vec<&str> = text.lines().collect();
+		let width = lines.first().map_or(0, |line| line.chars().count());
+		if width == 0 {
+			return Err(BoardParseError::new(1, 1, String::from("пустая доска")));
+		}
+
+		let mut board = Board::new(Size::new(width, lines.len()));
+		for (y, line) in lines.iter().enumerate() {
+			let mut line_width = 0;
+			for (x, ch) in line.chars().enumerate() {
+				if x >= width {
+					return Err(BoardParseError::new(y + 1, x + 1, format!("строка длиннее первой ({width})")));
+				}
+				match ch {
+					ASCII_EMPTY_CELL => {}
+					ASCII_FILLED_CELL => board.cells.set(y * width + x, true),
+					ch => return Err(BoardParseError::new(y + 1, x + 1, format!("неизвестный символ '{ch}'"))),
+				}
+				line_width += 1;
+			}
+			if line_width < width {
+				return Err(BoardParseError::new(y + 1, line_width + 1, format!("строка короче первой ({width})")));
+			}
+		}
+
+		Ok(board)
+	}
+
+	/// Текстовый вид доски для `from_ascii`: `#` и `.`, каждая строка заканчивается переводом строки
+	pub fn to_ascii(&self) -> String {
+		let mut text = String::with_capacity((self.size.width + 1) * self.size.height);
+		for row in self.cells.chunks(self.size.width) {
+			text.extend(row.iter().map(|cell| if *cell { ASCII_FILLED_CELL } else { ASCII_EMPTY_CELL }));
+			text.push('\n');
+		}
+		text
+	}
+
+	/// Доска, на которой клетка фигуры занимает `cell_scale`×`cell_scale` клеток.
+	/// Размер доски должен делиться на `cell_scale`.
+	pub fn with_cell_scale(mut self, cell_scale: usize) -> Self {
+		self.cell_scale = cell_scale.max(1);
+		self
+	}
+
+	pub fn with_clear_gravity(mut self, clear_gravity: ClearGravity) -> Self {
+		self.clear_gravity = clear_gravity;
+		self
+	}
+
+	pub fn clear_gravity(&self) -> ClearGravity {
+		self.clear_gravity
+	}
+
+	pub fn cell_scale(&self) -> usize {
+		self.cell_scale
+	}
+
+	/// Размер доски в логических клетках, в которых задаются позиции фигур
+	pub fn logical_size(&self) -> Size {
+		Size::new(self.size.width / self.cell_scale, self.size.height / self.cell_scale)
+	}
+
+	/// Игровое время, когда клетка была занята
+	pub fn lock_time(&self, x: usize, y: usize) -> Duration {
+		self.lock_times[y * self.size.width + x]
+	}
+
+	pub fn garbage_height(&self) -> usize {
+		self.garbage_height
+	}
+
+	/// Добавляет снизу строку мусора с дырами в переданных столбцах, сдвигая всё вверх.
+	/// Возвращает `false`, если заполненные клетки верхней строки вытолкнуло за доску.
+	pub fn push_garbage_line(&mut self, holes: &[usize], now: Duration) -> bool {
+		let width = self.size.width;
+		let fits = !self.cells[..width].any();
+
+		self.cells.drain(..width);
+		self.cells.extend((0..width).map(|x| !holes.contains(&x)));
+		self.lock_times.drain(..width);
+		self.lock_times.extend(iter::repeat_n(now, width));
+		self.garbage_height = (self.garbage_height + 1).min(self.size.height);
+
+		fits
+	}
+
+	/// Добавляет `count` строк мусора, в каждой — `holes_per_line` дыр в случайных столбцах.
+	/// Возвращает `false`, если заполненные клетки вытолкнуло за доску.
+	pub fn push_random_garbage_lines(&mut self, count: usize, holes_per_line: usize, rng: &mut impl Rng, now: Duration) -> bool {
+		let holes_per_line = holes_per_line.clamp(1, self.size.width);
+
+		let mut fits = true;
+		for _ in 0..count {
+			let holes = index::sample(rng, self.size.width, holes_per_line).into_vec();
+			fits &= self.push_garbage_line(&holes, now);
+		}
+		fits
+	}
+
+	/// Настоящие клетки доски, которые занимает логическая клетка (x, y)
+	fn scaled_cell_indices(&self, x: usize, y: usize) -> impl Iterator<Item = usize> + use<> {
+		let scale = self.cell_scale;
+		let width = self.size.width;
+		(0..scale * scale).map(move |i| (y * scale + i / scale) * width + x * scale + i % scale)
+	}
+
+	/// Занята ли логическая клетка (x, y); клетки за пределами доски считаются занятыми
+	pub fn is_blocked(&self, x: isize, y: isize) -> bool {
+		let Size { width, height } = self.logical_size();
+		if x < 0 || y < 0 || x as usize >= width || y as usize >= height {
+			return true;
+		}
+		self.scaled_cell_indices(x as usize, y as usize).any(|board_idx| self.cells[board_idx])
+	}
+
+	/// Проверяет, можно ли разместить фигуру по переданной позиции
+	/// (в пределах доски и без пересечения с заполненными клетками).
+	pub fn can_place(&self, figure: &Figure, pos: &Point) -> bool {
+		let Size { width: w, height: h } = self.logical_size();
+
+		for dy in 0..figure.size.height {
+			for dx in 0..figure.size.width {
+				let cell_idx = dy * figure.size.width + dx;
+				if !figure.cells[cell_idx] {
+					continue;
+				}
+
+				let x = pos.x + dx;
+				let y = pos.y + dy;
+
+				if x >= w || y >= h {
+					return false;
+				}
+
+				if self.scaled_cell_indices(x, y).any(|board_idx| self.cells[board_idx]) {
+					return false;
+				}
+			}
+		}
+		true
+	}
+
+	/// Возвращает позицию фигуры, если разместить её по переданной позиции
+	pub fn drop_position(&self, figure: &Figure, pos: &Point) -> Point {
+		let mut y = pos.y;
+		while self.can_place(figure, &Point::new(pos.x, y + 1)) {
+			y += 1;
+		}
+		Point::new(pos.x, y)
+	}
+
+	/// Размещает фигуру на доске (занимает клетки) в момент игрового времени `now`,
+	/// сразу проводит очистку заполненных линий. Возвращает количество убранных линий
+	/// на каждом шаге цепочки (пусто, если ничего не убрано); при `ClearGravity::Naive`
+	/// шаг не больше одного. Линии считаются в логических клетках (в «большом» режиме
+	/// пара строк доски — одна линия).
+	pub fn drop_figure(&mut self, figure: &Figure, pos: &Point, now: Duration) -> Vec<u8> {
+		let final_pos = self.drop_position(figure, pos);
+
+		for dy in 0..figure.size.height {
+			for dx in 0..figure.size.width {
+				let cell_idx = dy * figure.size.width + dx;
+				if !figure.cells[cell_idx] {
+					continue;
+				}
+				for board_idx in self.scaled_cell_indices(final_pos.x + dx, final_pos.y + dy) {
+					self.cells.set(board_idx, true);
+					self.lock_times[board_idx] = now;
+				}
+			}
+		}
+
+		let mut chain = Vec::new();
+		let mut cleared = self.clear_lines();
+		while cleared > 0 {
+			// Нечётное число настоящих строк в «большом» режиме дали бы мусор в одну
+			// строку или каскад; режимы их не сочетают
+			debug_assert!((cleared as usize).is_multiple_of(self.cell_scale), "убрано {cleared} строк при масштабе {}", self.cell_scale);
+			chain.push(cleared / self.cell_scale as u8);
+			if self.clear_gravity == ClearGravity::Naive {
+				break;
+			}
+			self.settle_chunks();
+			cleared = self.clear_lines();
+		}
+		chain
+	}
+
+	/// Связные (по сторонам) куски заполненных клеток, начиная с самых нижних
+	fn chunks(&self) -> Vec<Vec<usize>> {
+		let width = self.size.width;
+		let height = self.size.height;
+
+		let mut visited = bitvec![0; self.size.area()];
+		let mut chunks = Vec::new();
+		for start in self.cells.iter_ones() {
+			if visited[start] {
+				continue;
+			}
+			visited.set(start, true);
+
+			let mut chunk = Vec::new();
+			let mut stack = vec![start];
+			while let Some(idx) = stack.pop() {
+				chunk.push(idx);
+				let (x, y) = (idx % width, idx / width);
+				let neighbours = [
+					(x > 0).then(|| idx - 1),
+					(x + 1 < width).then(|| idx + 1),
+					(y > 0).then(|| idx - width),
+					(y + 1 < height).then(|| idx + width),
+				];
+				for neighbour in neighbours.into_iter().flatten() {
+					if self.cells[neighbour] && !visited[neighbour] {
+						visited.set(neighbour, true);
+						stack.push(neighbour);
+					}
+				}
+			}
+			chunks.push(chunk);
+		}
+
+		chunks.sort_by_key(|chunk| std::cmp::Reverse(chunk.iter().max().copied()));
+		chunks
+	}
+
+	/// Роняет связные куски клеток, пока все не лягут на дно или на другие клетки.
+	/// Клетки мусора падают вместе с кусками, после чего высота мусора пересчитывается
+	/// по самой верхней из них.
+	fn settle_chunks(&mut self) {
+		let width = self.size.width;
+		let height = self.size.height;
+
+		let garbage_start = (height - self.garbage_height) * width;
+		let mut garbage = self.cells.clone();
+		garbage[..garbage_start].fill(false);
+
+		loop {
+			let mut moved = false;
+			for chunk in self.chunks() {
+				let lock_times: Vec<Duration> = chunk.iter().map(|&idx| self.lock_times[idx]).collect();
+				let is_garbage: Vec<bool> = chunk.iter().map(|&idx| garbage[idx]).collect();
+				for &idx in &chunk {
+					self.cells.set(idx, false);
+					garbage.set(idx, false);
+				}
+
+				let can_fall_by = |fall: usize| chunk.iter().all(|&idx| {
+					let y = idx / width + fall;
+					y < height && !self.cells[idx + fall * width]
+				});
+				let mut fall = 0;
+				while can_fall_by(fall + 1) {
+					fall += 1;
+				}
+
+				for ((&idx, lock_time), is_garbage) in chunk.iter().zip(lock_times).zip(is_garbage) {
+					self.cells.set(idx + fall * width, true);
+					self.lock_times[idx + fall * width] = lock_time;
+					garbage.set(idx + fall * width, is_garbage);
+				}
+				moved |= fall > 0;
+			}
+
+			if !moved {
+				break;
+			}
+		}
+
+		self.garbage_height = garbage.first_one().map_or(0, |idx| height - idx / width);
+	}
+
+	/// Очищает заполненные линии, смещает существующие вниз, добавляет сверху новых.
+	/// Возвращает кол-во очищенных линий.
+	fn clear_lines(&mut self) -> u8 {
+		let width = self.size.width;
+		let height = self.size.height;
+
+		let garbage_start = height - self.garbage_height;
+
+		let mut kept_lines = Vec::new();
+		for y in 0..height {
+			let start = y * width;
+			let end = start + width;
+			let line = &self.cells[start..end];
+			if line.iter().all(|b| *b) {
+				if y >= garbage_start {
+					self.garbage_height -= 1;
+				}
+				continue;
+			}
+			kept_lines.push((line.to_bitvec(), &self.lock_times[start..end]));
+		}
+
+		let cleared = (height - kept_lines.len()) as u8;
+
+		let mut new_cells = BitVec::with_capacity(self.size.area());
+		let mut new_lock_times = Vec::with_capacity(self.size.area());
+		new_cells.extend(iter::repeat_n(false, cleared as usize * width));
+		new_lock_times.extend(iter::repeat_n(Duration::ZERO, cleared as usize * width));
+		for (line, lock_times) in kept_lines {
+			new_cells.extend(line);
+			new_lock_times.extend_from_slice(lock_times);
+		}
+
+		self.cells = new_cells;
+		self.lock_times = new_lock_times;
+		cleared
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use rand::SeedableRng;
+	use rand_chacha::ChaCha8Rng;
+
+	use super::*;
+	use crate::figure::FigureKind;
+
+	#[test]
+	fn cascade_settling_updates_garbage_height() {
+		let mut board = Board::new(Size::new(4, 4)).with_clear_gravity(ClearGravity::Cascade);
+		board.push_garbage_line(&[1, 2, 3], Duration::ZERO);
+		board.push_garbage_line(&[0], Duration::ZERO);
+		assert_eq!(board.to_ascii(), "....\n....\n#...\n.###\n");
+		assert_eq!(board.garbage_height(), 2);
+
+		// Линия над мусором убирается, клетка мусора падает в дыру и замыкает нижнюю строку
+		let figure = Figure::of_kind(FigureKind::I).rotated(true);
+		let chain = board.drop_figure(&figure, &Point::new(0, 0), Duration::ZERO);
+		assert_eq!(chain, vec![1, 1]);
+		assert!(!board.cells.any());
+		assert_eq!(board.garbage_height(), 0);
+	}
+
+	#[test]
+	fn ascii_round_trip() {
+		let text = "....\n.#..\n##.#\n";
+		let board = Board::from_ascii(text).unwrap();
+		assert_eq!(board.size, Size::new(4, 3));
+		assert_eq!(board.to_ascii(), text);
+		assert_eq!(Board::from_ascii(&board.to_ascii()).unwrap().cells, board.cells);
+	}
+
+	#[test]
+	fn ascii_errors_point_at_line_and_column() {
+		let error = Board::from_ascii("....\n.#x.\n").unwrap_err();
+		assert_eq!((error.line, error.column), (2, 3));
+
+		// Буквы фигур не пишутся `to_ascii`, поэтому и не читаются
+		let error = Board::from_ascii("....\nT...\n").unwrap_err();
+		assert_eq!((error.line, error.column), (2, 1));
+
+		let error = Board::from_ascii("....\n.....\n").unwrap_err();
+		assert_eq!((error.line, error.column), (2, 5));
+
+		let error = Board::from_ascii("....\n##\n").unwrap_err();
+		assert_eq!((error.line, error.column), (2, 3));
+
+		let error = Board::from_ascii("").unwrap_err();
+		assert_eq!((error.line, error.column), (1, 1));
+	}
+
+	#[test]
+	fn garbage_lines_shift_rows_up() {
+		let mut board = Board::from_ascii("....\n....\n.#..\n").unwrap();
+		assert!(board.push_garbage_line(&[2], Duration::ZERO));
+		assert_eq!(board.to_ascii(), "....\n.#..\n##.#\n");
+		assert_eq!(board.garbage_height(), 1);
+
+		let mut board = Board::new(Size::new(10, 20));
+		let mut rng = ChaCha8Rng::seed_from_u64(1);
+		assert!(board.push_random_garbage_lines(5, 1, &mut rng, Duration::ZERO));
+		assert_eq!(board.garbage_height(), 5);
+		let text = board.to_ascii();
+		let rows: Vec<&str> = text.lines().collect();
+		assert!(rows[..15].iter().all(|row| !row.contains('#')));
+		assert!(rows[15..].iter().all(|row| row.matches('.').count() == 1), "{text}");
+	}
+
+	#[test]
+	fn clearing_garbage_lowers_its_height() {
+		let mut board = Board::new(Size::new(4, 8));
+		board.push_garbage_line(&[0, 1], Duration::ZERO);
+		board.push_garbage_line(&[0], Duration::ZERO);
+		assert_eq!(board.garbage_height(), 2);
+
+		// Палка закрывает дыру только в нижней строке мусора
+		let chain = board.drop_figure(&Figure::of_kind(FigureKind::I), &Point::new(0, 0), Duration::ZERO);
+		assert_eq!(chain, vec![1]);
+		assert_eq!(board.garbage_height(), 1);
+		assert!(board.to_ascii().ends_with("#.##\n"));
+	}
+
+	#[test]
+	fn garbage_reports_top_out() {
+		let mut board = Board::from_ascii("#...\n....\n").unwrap();
+		assert!(!board.push_garbage_line(&[0], Duration::ZERO));
+		let mut board = Board::from_ascii("....\n#...\n").unwrap();
+		assert!(board.push_garbage_line(&[0], Duration::ZERO));
+	}
+}
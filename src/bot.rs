@@ -0,0 +1,335 @@
+use std::time::Duration;
+
+use crossterm::event::KeyCode;
+
+use crate::shared::*;
+use crate::input::*;
+use crate::ui::*;
+use crate::state::*;
+use crate::render::Frame;
+use crate::spectator::SpectatorFrame;
+use crate::board::Board;
+use crate::figure::Figure;
+use crate::movegen::{self, Placement};
+use crate::mode::GameMode;
+use crate::game::{GameEvent, GameState};
+use crate::menu::MenuState;
+
+/// Веса признаков доски после фиксации фигуры. Чем больше оценка, тем лучше ход.
+#[derive(Debug, Clone, Copy)]
+pub struct Weights {
+	/// Сумма высот столбцов
+	pub aggregate_height: f64,
+	/// Убранные линии
+	pub complete_lines: f64,
+	/// Пустые клетки под верхом своего столбца
+	pub holes: f64,
+	/// Сумма перепадов высот соседних столбцов
+	pub bumpiness: f64,
+	/// Сумма глубин колодцев (столбцов ниже обоих соседей)
+	pub wells: f64,
+}
+
+impl Default for Weights {
+	fn default() -> Self {
+		Self {
+			aggregate_height: -0.51,
+			complete_lines: 0.76,
+			holes: -0.36,
+			bumpiness: -0.18,
+			wells: -0.05,
+		}
+	}
+}
+
+impl Weights {
+	pub fn evaluate(&self, board: &Board, lines_cleared: u8) -> f64 {
+		let size = board.logical_size();
+		let heights: Vec<usize> = (0..size.width)
+			.map(|x| (0..size.height)
+				.find(|&y| board.is_blocked(x as isize, y as isize))
+				.map_or(0, |top| size.height - top))
+			.collect();
+
+		let holes: usize = (0..size.width)
+			.map(|x| (size.height - heights[x]..size.height)
+				.filter(|&y| !board.is_blocked(x as isize, y as isize))
+				.count())
+			.sum();
+
+		let bumpiness: usize = heights.windows(2)
+			.map(|pair| pair[0].abs_diff(pair[1]))
+			.sum();
+
+		// Стены считаются бесконечно высокими соседями
+		let wells: usize = (0..size.width)
+			.map(|x| {
+				let left = if x == 0 { usize::MAX } else { heights[x - 1] };
+				let right = heights.get(x + 1).copied().unwrap_or(usize::MAX);
+				left.min(right).saturating_sub(heights[x]).min(size.height)
+			})
+			.sum();
+
+		self.aggregate_height * heights.iter().sum::<usize>() as f64
+			+ self.complete_lines * lines_cleared as f64
+			+ self.holes * holes as f64
+			+ self.bumpiness * bumpiness as f64
+			+ self.wells * wells as f64
+	}
+
+	/// Оценка доски после постановки
+	pub fn evaluate_placement(&self, board: &Board, placement: &Placement) -> f64 {
+		let mut result = board.clone();
+		let lines_cleared = result.drop_figure(&placement.figure, &placement.position, Duration::ZERO).iter().sum();
+		self.evaluate(&result, lines_cleared)
+	}
+
+	/// Лучшая из достижимых постановок фигуры (см. `movegen::reachable_placements`);
+	/// `None`, если ходов нет
+	pub fn best_placement(&self, board: &Board, figure: &Figure, position: Point) -> Option<Placement> {
+		movegen::reachable_placements(board, figure, position)
+			.into_iter()
+			.map(|placement| (self.evaluate_placement(board, &placement), placement))
+			.max_by(|(left, _), (right, _)| left.total_cmp(right))
+			.map(|(_, placement)| placement)
+	}
+}
+
+/// Программа, играющая вместо человека: по состоянию игры выдаёт действия
+pub trait BotPlayer {
+	/// Следующее действие, если пора ходить
+	fn update(&mut self, game: &GameState, delta: Duration) -> Option<PlayerAction>;
+	/// Забывает план прошлой партии перед новой
+	fn reset(&mut self);
+}
+
+/// Бот, который ведёт игру: на каждую фигуру выбирает постановку и выдаёт
+/// действия к ней по одному, не чаще `action_interval`. Путь к постановке
+/// ищется заново от текущего положения фигуры перед каждым действием, поэтому
+/// гравитация, сдвинувшая фигуру между действиями, его не сбивает.
+pub struct Bot {
+	weights: Weights,
+	action_interval: Duration,
+	since_last_action: Duration,
+	/// Клетки, которые должна занять текущая фигура
+	target: Option<Vec<Point>>,
+	/// Для какой по счёту фигуры выбрана постановка
+	planned_piece: Option<u32>,
+}
+
+impl Bot {
+	pub const DEFAULT_ACTION_INTERVAL: Duration = Duration::from_millis(80);
+
+	pub fn new() -> Self {
+		Self {
+			weights: Weights::default(),
+			action_interval: Self::DEFAULT_ACTION_INTERVAL,
+			since_last_action: Duration::ZERO,
+			target: None,
+			planned_piece: None,
+		}
+	}
+
+	pub fn with_weights(mut self, weights: Weights) -> Self {
+		self.weights = weights;
+		self
+	}
+
+	pub fn with_action_interval(mut self, action_interval: Duration) -> Self {
+		self.action_interval = action_interval;
+		self
+	}
+
+	/// Следующее действие на пути к выбранной постановке. Если до неё уже
+	/// не добраться (фигуру снесло гравитацией), выбирается лучшая из оставшихся.
+	fn next_action(&mut self, game: &GameState) -> PlayerAction {
+		let placements = movegen::reachable_placements(game.board(), game.current_figure(), game.current_position());
+		let planned = placements.iter()
+			.find(|placement| self.target.as_ref() == Some(&placement.cells()));
+		let placement = match planned {
+			Some(placement) => placement,
+			None => {
+				let Some((_, placement)) = placements.iter()
+					.map(|placement| (self.weights.evaluate_placement(game.board(), placement), placement))
+					.max_by(|(left, _), (right, _)| left.total_cmp(right))
+				else {
+					return PlayerAction::Drop;
+				};
+				self.target = Some(placement.cells());
+				placement
+			}
+		};
+		placement.actions[0]
+	}
+}
+
+impl BotPlayer for Bot {
+	fn update(&mut self, game: &GameState, delta: Duration) -> Option<PlayerAction> {
+		if game.is_paused() || game.is_game_over() {
+			return None;
+		}
+
+		// Фигура сменилась (в том числе упала сама, не дойдя до цели) — выбираем постановку заново
+		if self.planned_piece != Some(game.pieces_placed()) {
+			self.planned_piece = Some(game.pieces_placed());
+			self.target = self.weights
+				.best_placement(game.board(), game.current_figure(), game.current_position())
+				.map(|placement| placement.cells());
+		}
+
+		self.since_last_action += delta;
+		if self.since_last_action < self.action_interval {
+			return None;
+		}
+		self.since_last_action = Duration::ZERO;
+		Some(self.next_action(game))
+	}
+
+	fn reset(&mut self) {
+		self.since_last_action = Duration::ZERO;
+		self.target = None;
+		self.planned_piece = None;
+	}
+}
+
+impl Default for Bot {
+	fn default() -> Self {
+		Self::new()
+	}
+}
+
+/// Автоигра: бот играет марафон, человек смотрит
+pub struct AutoplayState {
+	game: GameState,
+	bot: Box<dyn BotPlayer>,
+}
+
+impl AutoplayState {
+	pub fn new(seed: u64) -> Self {
+		Self {
+			game: GameState::new(GameMode::Marathon, 0, seed),
+			bot: Box::new(Bot::new()),
+		}
+	}
+
+	pub fn with_bot(mut self, bot: Box<dyn BotPlayer>) -> Self {
+		self.bot = bot;
+		self
+	}
+
+	fn restart(&mut self) {
+		self.game = GameState::new(GameMode::Marathon, 0, rand::random());
+		self.bot.reset();
+	}
+}
+
+impl State for AutoplayState {
+	fn update(&mut self, context: &UpdateContext) -> std::io::Result<NextUpdateAction> {
+		for key_event in collect_last_key_events()? {
+			if key_event.is_release() {
+				continue;
+			}
+			if is_interrupt(&key_event) {
+				return Ok(NextUpdateAction::Exit);
+			}
+
+			match key_event.code {
+				KeyCode::Esc => return Ok(NextUpdateAction::Switch(Box::new(MenuState::new()))),
+				KeyCode::Enter if self.game.is_game_over() => self.restart(),
+				KeyCode::Char('p') | KeyCode::Char('з') => self.game.toggle_pause(),
+				_ => {}
+			}
+		}
+
+		self.game.advance_time(context.delta_time);
+		if let Some(action) = self.bot.update(&self.game, context.delta_time) {
+			self.game.apply_event(GameEvent::Action(action));
+		}
+		if self.game.is_gravity_due() {
+			self.game.apply_event(GameEvent::Gravity);
+		}
+
+		Ok(NextUpdateAction::Continue)
+	}
+
+	fn render_frame(&self, frame: &mut Frame) {
+		const GAP_BETWEEN_PARTS: usize = 4;
+
+		let mut game_part = Frame::new();
+		self.game.render_frame(&mut game_part);
+
+		let mut info_part = vec![String::from("АВТОИГРА"), String::new()];
+		if self.game.is_game_over() {
+			info_part.push(String::from("ИГРА ОКОНЧЕНА"));
+			info_part.push(String::from("Enter — заново"));
+		} else {
+			info_part.push(format!("ФИГУРЫ: {}", self.game.pieces_placed()));
+			info_part.push(String::from("P — пауза"));
+		}
+		info_part.push(String::from("Esc — в меню"));
+
+		push_parts_side_by_side(frame, &game_part, &Frame::from_lines(&info_part), GAP_BETWEEN_PARTS);
+	}
+
+	fn spectator_frame(&self) -> Option<SpectatorFrame> {
+		self.game.spectator_frame()
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::figure::FigureKind;
+
+	/// Ведёт текущую фигуру ботом до фиксации; возвращает клетки, куда она легла
+	fn play_piece(bot: &mut Bot, game: &mut GameState) -> Vec<Point> {
+		let piece = game.pieces_placed();
+		for _ in 0..100 {
+			let figure = game.current_figure().clone();
+			let landing = game.board().drop_position(&figure, &game.current_position());
+			let action = bot.update(game, Duration::ZERO).unwrap();
+			game.apply_event(GameEvent::Action(action));
+			if game.pieces_placed() != piece {
+				return movegen::figure_cells(&figure, landing);
+			}
+		}
+		panic!("бот не поставил фигуру за 100 действий");
+	}
+
+	#[test]
+	fn reaches_target_after_gravity_moved_piece() {
+		let mut game = GameState::new(GameMode::Marathon, 0, 1);
+		let mut bot = Bot::new().with_action_interval(Duration::ZERO);
+		bot.update(&game, Duration::ZERO);
+		let target = bot.target.clone().unwrap();
+
+		for _ in 0..10 {
+			game.apply_event(GameEvent::Gravity);
+		}
+		assert_eq!(play_piece(&mut bot, &mut game), target);
+	}
+
+	#[test]
+	fn replans_when_target_becomes_unreachable() {
+		let mut text = String::from("..........\n").repeat(2);
+		text.push_str(&"###....###\n".repeat(18));
+		let board = Board::from_ascii(&text).unwrap();
+		let mut game = (0..)
+			.map(|seed| GameState::new(GameMode::Marathon, 0, seed).with_board(board.clone()))
+			.find(|game| game.current_figure().kind == FigureKind::O)
+			.unwrap();
+		let mut bot = Bot::new().with_action_interval(Duration::ZERO);
+		bot.update(&game, Duration::ZERO);
+
+		// На левую стенку можно попасть только сверху, а гравитация утащила фигуру в колодец
+		let on_wall = vec![Point::new(0, 0), Point::new(1, 0), Point::new(0, 1), Point::new(1, 1)];
+		bot.target = Some(on_wall.clone());
+		for _ in 0..5 {
+			game.apply_event(GameEvent::Gravity);
+		}
+
+		let landing = play_piece(&mut bot, &mut game);
+		assert_ne!(landing, on_wall);
+		assert!(landing.iter().all(|cell| (3..=6).contains(&cell.x) && cell.y >= 18));
+	}
+}
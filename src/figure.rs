@@ -0,0 +1,171 @@
+use bitvec::prelude::*;
+use rand::{Rng, seq::IndexedRandom};
+use serde::{Serialize, Deserialize};
+
+use crate::shared::*;
+
+pub type FigureCells = BitArray<[u8; 1]>;
+
+/// Вид фигуры, по общепринятым буквам
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum FigureKind {
+	I,
+	J,
+	L,
+	T,
+	S,
+	Z,
+	O,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Figure {
+	pub kind: FigureKind,
+	pub size: Size,
+	pub cells: FigureCells,
+}
+impl Figure {
+	const fn new(kind: FigureKind, size: Size, cells: FigureCells) -> Self {
+		Self { kind, size, cells }
+	}
+
+	/// Фигура заданного вида в начальном положении
+	pub fn of_kind(kind: FigureKind) -> Self {
+		Self::BASE_FIGURES.iter()
+			.find(|figure| figure.kind == kind)
+			.unwrap()
+			.clone()
+	}
+
+	pub fn rotated(&self, by_clockwise: bool) -> Self {
+		let old_h = self.size.height;
+		let old_w = self.size.width;
+		let new_h = old_w;
+		let new_w = old_h;
+
+		let mut new_cells = FigureCells::ZERO;
+		for y in 0..old_h {
+			for x in 0..old_w {
+				if self.cells[y * old_w + x] {
+					let new_x; let new_y;
+					if by_clockwise {
+						new_x = old_h - 1 - y;
+						new_y = x;
+					} else {
+						new_x = y;
+						new_y = new_h - 1 - x;
+					}
+
+					new_cells.set(new_y * new_w + new_x, true);
+				}
+			}
+		}
+
+		let size = Size { height: new_h, width: new_w };
+		let cells = new_cells;
+
+		Self { kind: self.kind, size, cells }
+	}
+
+	const BASE_FIGURES: [Figure; 7] = [
+		Figure::new(
+			FigureKind::I,
+			Size { height: 4, width: 1 },
+			bitarr![const u8, Lsb0; 1, 1, 1, 1]
+		),
+		Figure::new(
+			FigureKind::J,
+			Size { height: 3, width: 2 },
+			bitarr![const u8, Lsb0;
+				0, 1,
+				0, 1,
+				1, 1,
+			]
+		),
+		Figure::new(
+			FigureKind::L,
+			Size { height: 3, width: 2 },
+			bitarr![const u8, Lsb0;
+				1, 0,
+				1, 0,
+				1, 1,
+			]
+		),
+		Figure::new(
+			FigureKind::T,
+			Size { height: 2, width: 3 },
+			bitarr![const u8, Lsb0;
+				1, 1, 1,
+				0, 1, 0,
+			]
+		),
+		Figure::new(
+			FigureKind::S,
+			Size { height: 2, width: 3 },
+			bitarr![const u8, Lsb0;
+				0, 1, 1,
+				1, 1, 0,
+			]
+		),
+		Figure::new(
+			FigureKind::Z,
+			Size { height: 2, width: 3 },
+			bitarr![const u8, Lsb0;
+				1, 1, 0,
+				0, 1, 1,
+			]
+		),
+		Figure::new(
+			FigureKind::O,
+			Size { height: 2, width: 2 },
+			bitarr![const u8, Lsb0;
+				1, 1,
+				1, 1,
+			]
+		),
+	];
+
+	pub fn choose_random(rng: &mut impl Rng) -> Self {
+		Self::BASE_FIGURES.choose(rng).unwrap().clone()
+	}
+
+	/// Покрывает ли фигура (в позиции pos) клетку доски (row, col), если
+	/// клетка фигуры занимает `cell_scale`×`cell_scale` клеток доски
+	pub fn covers(&self, row: usize, col: usize, pos: &Point, cell_scale: usize) -> bool {
+		let row = row / cell_scale;
+		let col = col / cell_scale;
+		if row < pos.y || row >= pos.y + self.size.height {
+			return false;
+		}
+		if col < pos.x || col >= pos.x + self.size.width {
+			return false;
+		}
+		let dx = col - pos.x;
+		let dy = row - pos.y;
+		let idx = dy * self.size.width + dx;
+		self.cells[idx]
+	}
+
+	/// Центральная клетка T-фигуры (та, у которой три соседа), относительно её позиции.
+	/// Для остальных фигур — `None`.
+	pub fn t_center(&self) -> Option<Point> {
+		if self.kind != FigureKind::T {
+			return None;
+		}
+
+		let is_filled = |x: usize, y: usize| x < self.size.width && y < self.size.height
+			&& self.cells[y * self.size.width + x];
+
+		(0..self.size.height)
+			.flat_map(|y| (0..self.size.width).map(move |x| Point::new(x, y)))
+			.find(|cell| {
+				let neighbours = [
+					cell.x > 0 && is_filled(cell.x - 1, cell.y),
+					is_filled(cell.x + 1, cell.y),
+					cell.y > 0 && is_filled(cell.x, cell.y - 1),
+					is_filled(cell.x, cell.y + 1),
+				];
+				is_filled(cell.x, cell.y) && neighbours.iter().filter(|filled| **filled).count() == 3
+			})
+	}
+}
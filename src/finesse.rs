@@ -0,0 +1,143 @@
+use serde::{Serialize, Deserialize};
+
+use crate::shared::*;
+use crate::board::Board;
+use crate::figure::{Figure, FigureKind};
+use crate::movegen;
+
+/// Разбор одной постановки: сколько нажатий сделано и сколько было нужно
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct FinesseCheck {
+	pub kind: FigureKind,
+	pub inputs: u16,
+	pub optimal: u16,
+}
+
+impl FinesseCheck {
+	pub fn is_fault(&self) -> bool {
+		self.inputs > self.optimal
+	}
+
+	pub fn extra_inputs(&self) -> u16 {
+		self.inputs.saturating_sub(self.optimal)
+	}
+}
+
+/// Сравнивает нажатия игрока с кратчайшим путём к той же постановке от спавна.
+/// `None`, если постановка недостижима (например, доска изменилась под фигурой).
+pub fn check_placement(
+	board: &Board,
+	spawn_figure: &Figure,
+	spawn_position: Point,
+	figure: &Figure,
+	position: Point,
+	inputs: u16,
+) -> Option<FinesseCheck> {
+	let target = movegen::figure_cells(figure, position);
+	movegen::reachable_placements(board, spawn_figure, spawn_position)
+		.into_iter()
+		.find(|placement| placement.cells() == target)
+		.map(|placement| FinesseCheck {
+			kind: figure.kind,
+			inputs,
+			optimal: placement.actions.len() as u16,
+		})
+}
+
+/// Итоги по одному виду фигур
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+pub struct KindStats {
+	pub pieces: u32,
+	pub faults: u32,
+	pub extra_inputs: u32,
+}
+
+/// Итоги тренировки техники
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct FinesseStats {
+	pub faults: u32,
+	/// Разбор последней постановки, показывается сразу после неё
+	pub last_check: Option<FinesseCheck>,
+	/// В порядке первого появления вида фигуры
+	pub by_kind: Vec<(FigureKind, KindStats)>,
+}
+
+impl FinesseStats {
+	pub fn record(&mut self, check: FinesseCheck) {
+		let index = match self.by_kind.iter().position(|(kind, _)| *kind == check.kind) {
+			Some(index) => index,
+			None => {
+				self.by_kind.push((check.kind, KindStats::default()));
+				self.by_kind.len() - 1
+			}
+		};
+		let stats = &mut self.by_kind[index].1;
+		stats.pieces += 1;
+		if check.is_fault() {
+			stats.faults += 1;
+			stats.extra_inputs += check.extra_inputs() as u32;
+			self.faults += 1;
+		}
+		self.last_check = Some(check);
+	}
+
+	/// Строки итогов по видам фигур для экрана конца игры
+	pub fn summary_lines(&self) -> Vec<String> {
+		self.by_kind.iter()
+			.map(|(kind, stats)| format!(
+				"{kind:?}: {} фиг., ошибок {}, лишних нажатий {}",
+				stats.pieces, stats.faults, stats.extra_inputs,
+			))
+			.collect()
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::figure::FigureKind;
+
+	const SPAWN: Point = Point::new(5, 0);
+
+	fn optimal(kind: FigureKind, rotations: usize, position: Point) -> u16 {
+		let board = Board::new(Size::new(10, 20));
+		let mut figure = Figure::of_kind(kind);
+		for _ in 0..rotations {
+			figure = figure.rotated(true);
+		}
+		check_placement(&board, &Figure::of_kind(kind), SPAWN, &figure, position, 0)
+			.expect("постановка на пустой доске достижима")
+			.optimal
+	}
+
+	#[test]
+	fn knows_optimal_input_counts() {
+		// Сброс прямо со спавна
+		assert_eq!(optimal(FigureKind::O, 0, Point::new(5, 18)), 1);
+		// Пять шагов к стене и сброс
+		assert_eq!(optimal(FigureKind::O, 0, Point::new(0, 18)), 6);
+		assert_eq!(optimal(FigureKind::I, 0, Point::new(9, 16)), 5);
+		// Поворот, пять шагов и сброс
+		assert_eq!(optimal(FigureKind::I, 1, Point::new(0, 19)), 7);
+		// Два поворота в любую сторону, пять шагов и сброс
+		assert_eq!(optimal(FigureKind::T, 2, Point::new(0, 18)), 8);
+		// Против часовой один поворот вместо трёх
+		assert_eq!(optimal(FigureKind::J, 3, Point::new(5, 18)), 2);
+	}
+
+	#[test]
+	fn counts_extra_inputs() {
+		let board = Board::new(Size::new(10, 20));
+		let figure = Figure::of_kind(FigureKind::O);
+		let check = check_placement(&board, &figure, SPAWN, &figure, Point::new(0, 18), 9).unwrap();
+		assert!(check.is_fault());
+		assert_eq!(check.extra_inputs(), 3);
+
+		let mut stats = FinesseStats::default();
+		stats.record(check);
+		stats.record(FinesseCheck { inputs: 6, ..check });
+		assert_eq!(stats.faults, 1);
+		assert_eq!(stats.by_kind[0].1.pieces, 2);
+		assert_eq!(stats.by_kind[0].1.extra_inputs, 3);
+	}
+}
@@ -0,0 +1,820 @@
+use std::iter;
+
+use crossterm::event::KeyCode;
+
+use crate::shared::*;
+use crate::input::*;
+use crate::ui::*;
+use crate::state::*;
+use crate::render::Frame;
+use crate::board::Board;
+use crate::figure::{Figure, FigureKind};
+use crate::game::{BOARD_SIZE, GameState};
+use crate::mode::GameMode;
+use crate::menu::MenuState;
+
+const PREFIX: &str = "v115@";
+const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+/// Символы, допустимые в комментарии после `escape`; упаковываются по 4 в 5 цифр
+const COMMENT_TABLE: &str = " !\"#$%&'()*+,-./0123456789:;<=>?@ABCDEFGHIJKLMNOPQRSTUVWXYZ[\\]^_`abcdefghijklmnopqrstuvwxyz{|}~";
+const MAX_COMMENT_LENGTH: usize = 4095;
+
+const FIELD_WIDTH: usize = 10;
+/// Высота игрового поля fumen, без строки мусора под ним
+const FIELD_TOP: usize = 23;
+/// Клетки поля вместе со строкой мусора
+const FIELD_BLOCKS: usize = (FIELD_TOP + 1) * FIELD_WIDTH;
+
+const EMPTY_BLOCK: u8 = 0;
+const GRAY_BLOCK: u8 = 8;
+
+/// Поворот фигуры в обозначениях fumen
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FumenRotation {
+	Spawn,
+	Right,
+	Reverse,
+	Left,
+}
+
+impl FumenRotation {
+	const ALL: [FumenRotation; 4] = [FumenRotation::Spawn, FumenRotation::Right, FumenRotation::Reverse, FumenRotation::Left];
+
+	fn code(self) -> u32 {
+		match self {
+			FumenRotation::Reverse => 0,
+			FumenRotation::Right => 1,
+			FumenRotation::Spawn => 2,
+			FumenRotation::Left => 3,
+		}
+	}
+
+	fn from_code(code: u32) -> Self {
+		match code {
+			0 => FumenRotation::Reverse,
+			1 => FumenRotation::Right,
+			2 => FumenRotation::Spawn,
+			_ => FumenRotation::Left,
+		}
+	}
+
+	/// Поворачивает смещение клетки от центра фигуры (ось y вверх)
+	fn apply(self, (x, y): (i32, i32)) -> (i32, i32) {
+		match self {
+			FumenRotation::Spawn => (x, y),
+			FumenRotation::Right => (y, -x),
+			FumenRotation::Reverse => (-x, -y),
+			FumenRotation::Left => (-y, x),
+		}
+	}
+}
+
+fn piece_code(kind: FigureKind) -> u8 {
+	match kind {
+		FigureKind::I => 1,
+		FigureKind::L => 2,
+		FigureKind::O => 3,
+		FigureKind::Z => 4,
+		FigureKind::T => 5,
+		FigureKind::J => 6,
+		FigureKind::S => 7,
+	}
+}
+
+fn piece_from_code(code: u32) -> Option<FigureKind> {
+	match code {
+		1 => Some(FigureKind::I),
+		2 => Some(FigureKind::L),
+		3 => Some(FigureKind::O),
+		4 => Some(FigureKind::Z),
+		5 => Some(FigureKind::T),
+		6 => Some(FigureKind::J),
+		7 => Some(FigureKind::S),
+		_ => None,
+	}
+}
+
+/// Смещения клеток фигуры в начальном положении от её центра, ось y вверх
+fn spawn_offsets(kind: FigureKind) -> [(i32, i32); 4] {
+	match kind {
+		FigureKind::I => [(0, 0), (-1, 0), (1, 0), (2, 0)],
+		FigureKind::T => [(0, 0), (-1, 0), (1, 0), (0, 1)],
+		FigureKind::O => [(0, 0), (1, 0), (0, 1), (1, 1)],
+		FigureKind::L => [(0, 0), (-1, 0), (1, 0), (1, 1)],
+		FigureKind::J => [(0, 0), (-1, 0), (1, 0), (-1, 1)],
+		FigureKind::S => [(0, 0), (-1, 0), (0, 1), (1, 1)],
+		FigureKind::Z => [(0, 0), (1, 0), (0, 1), (-1, 1)],
+	}
+}
+
+/// Сдвиг от хранимой в fumen позиции фигуры к центру, вокруг которого она
+/// поворачивается: у некоторых фигур и поворотов они не совпадают
+fn stored_center_shift(kind: FigureKind, rotation: FumenRotation) -> (i32, i32) {
+	use FumenRotation::*;
+	match (kind, rotation) {
+		(FigureKind::O, Left) => (1, -1),
+		(FigureKind::O, Reverse) => (1, 0),
+		(FigureKind::O, Spawn) => (0, -1),
+		(FigureKind::I, Reverse) => (1, 0),
+		(FigureKind::I, Left) => (0, -1),
+		(FigureKind::S, Spawn) => (0, -1),
+		(FigureKind::S, Right) => (-1, 0),
+		(FigureKind::Z, Spawn) => (0, -1),
+		(FigureKind::Z, Left) => (1, 0),
+		_ => (0, 0),
+	}
+}
+
+/// Фигура страницы: вид, поворот и центр в координатах fumen
+/// (x слева направо, y снизу вверх, 0 — нижняя строка)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FumenOperation {
+	pub kind: FigureKind,
+	pub rotation: FumenRotation,
+	pub x: i32,
+	pub y: i32,
+}
+
+impl FumenOperation {
+	/// Клетки фигуры в координатах fumen
+	fn cells(&self) -> [(i32, i32); 4] {
+		spawn_offsets(self.kind).map(|offset| {
+			let (dx, dy) = self.rotation.apply(offset);
+			(self.x + dx, self.y + dy)
+		})
+	}
+
+	/// Фигура и позиция её левого верхнего угла на доске высотой `board_height`.
+	/// `None`, если фигура выходит за доску.
+	pub fn to_placement(&self, board_height: usize) -> Option<(Figure, Point)> {
+		let cells = self.cells();
+		if cells.iter().any(|&(x, y)| x < 0 || y < 0 || x >= FIELD_WIDTH as i32 || y >= board_height as i32) {
+			return None;
+		}
+
+		let left = cells.iter().map(|&(x, _)| x).min()?;
+		let top = cells.iter().map(|&(_, y)| y).max()?;
+		let position = Point::new(left as usize, board_height - 1 - top as usize);
+
+		// Поворот нашей фигуры с той же формой
+		let mut figure = Figure::of_kind(self.kind);
+		for _ in 0..4 {
+			if cells.iter().all(|&(x, y)| figure.covers(board_height - 1 - y as usize, x as usize, &position, 1)) {
+				return Some((figure, position));
+			}
+			figure = figure.rotated(true);
+		}
+		None
+	}
+
+	/// Операция для фигуры, стоящей левым верхним углом в `position` на доске высотой `board_height`
+	pub fn from_placement(figure: &Figure, position: &Point, board_height: usize) -> Self {
+		let mut cells: Vec<(i32, i32)> = Vec::new();
+		for dy in 0..figure.size.height {
+			for dx in 0..figure.size.width {
+				if figure.cells[dy * figure.size.width + dx] {
+					let row = position.y + dy;
+					cells.push(((position.x + dx) as i32, board_height as i32 - 1 - row as i32));
+				}
+			}
+		}
+		cells.sort();
+
+		// Центр подбирается так, чтобы клетки поворота совпали с клетками фигуры
+		for rotation in FumenRotation::ALL {
+			let mut offsets = spawn_offsets(figure.kind).map(|offset| rotation.apply(offset));
+			offsets.sort();
+			let (x, y) = (cells[0].0 - offsets[0].0, cells[0].1 - offsets[0].1);
+			let operation = FumenOperation { kind: figure.kind, rotation, x, y };
+
+			let mut operation_cells = operation.cells();
+			operation_cells.sort();
+			if operation_cells[..] == cells[..] {
+				return operation;
+			}
+		}
+		unreachable!("у каждой фигуры есть поворот fumen с той же формой")
+	}
+}
+
+/// Страница fumen в наших понятиях
+#[derive(Debug, Clone)]
+pub struct FumenPage {
+	/// Доска в начале страницы, до операции
+	pub board: Board,
+	pub operation: Option<FumenOperation>,
+	/// Комментарий страницы; в fumen он переходит на следующие страницы, пока не сменится
+	pub comment: Option<String>,
+}
+
+impl FumenPage {
+	pub fn new(board: Board) -> Self {
+		Self { board, operation: None, comment: None }
+	}
+}
+
+/// Поле fumen: 23 строки сверху вниз и строка мусора под ними; значение — цвет клетки
+#[derive(Clone, PartialEq)]
+struct Field([u8; FIELD_BLOCKS]);
+
+impl Field {
+	fn empty() -> Self {
+		Self([EMPTY_BLOCK; FIELD_BLOCKS])
+	}
+
+	/// Индекс клетки; y = -1 — строка мусора
+	fn index(x: usize, y: i32) -> usize {
+		(FIELD_TOP as i32 - 1 - y) as usize * FIELD_WIDTH + x
+	}
+
+	/// Поле из доски. Клетки, занятые и там и там, берут цвет из `colors`,
+	/// чтобы разница с предыдущим полем была минимальной.
+	fn from_board(board: &Board, colors: &Field) -> Self {
+		let mut field = Self::empty();
+		for row in 0..board.size.height {
+			let y = (board.size.height - 1 - row) as i32;
+			for x in 0..board.size.width {
+				if board.cells[row * board.size.width + x] {
+					let color = colors.0[Self::index(x, y)];
+					field.0[Self::index(x, y)] = if color == EMPTY_BLOCK { GRAY_BLOCK } else { color };
+				}
+			}
+		}
+		field
+	}
+
+	fn to_board(&self, size: Size) -> Result<Board, String> {
+		let mut board = Board::new(size);
+		for y in 0..FIELD_TOP as i32 {
+			for x in 0..FIELD_WIDTH {
+				if self.0[Self::index(x, y)] == EMPTY_BLOCK {
+					continue;
+				}
+				if y as usize >= size.height {
+					return Err(format!("клетка ({x}, {y}) выше доски из {} строк", size.height));
+				}
+				let row = size.height - 1 - y as usize;
+				board.cells.set(row * size.width + x, true);
+			}
+		}
+		Ok(board)
+	}
+
+	/// Фиксирует фигуру, убирает заполненные строки, при необходимости поднимает
+	/// мусор и отражает поле — как fumen при переходе к следующей странице
+	fn apply_lock(&mut self, operation: Option<&FumenOperation>, rise: bool, mirror: bool) {
+		if let Some(operation) = operation {
+			for (x, y) in operation.cells() {
+				if (0..FIELD_WIDTH as i32).contains(&x) && (0..FIELD_TOP as i32).contains(&y) {
+					self.0[Self::index(x as usize, y)] = piece_code(operation.kind);
+				}
+			}
+		}
+
+		let rows: Vec<[u8; FIELD_WIDTH]> = (0..FIELD_TOP as i32)
+			.map(|y| std::array::from_fn(|x| self.0[Self::index(x, y)]))
+			.filter(|row| row.contains(&EMPTY_BLOCK))
+			.collect();
+		let garbage: [u8; FIELD_WIDTH] = std::array::from_fn(|x| self.0[Self::index(x, -1)]);
+
+		// Строки снизу вверх; при подъёме мусор становится нижней строкой
+		let rows: Vec<[u8; FIELD_WIDTH]> = match rise {
+			true => iter::once(garbage).chain(rows).collect(),
+			false => rows,
+		};
+
+		let mut field = Self::empty();
+		for (y, row) in rows.iter().take(FIELD_TOP).enumerate() {
+			for (x, color) in row.iter().enumerate() {
+				let x = if mirror { FIELD_WIDTH - 1 - x } else { x };
+				field.0[Self::index(x, y as i32)] = *color;
+			}
+		}
+		if !rise {
+			for (x, color) in garbage.iter().enumerate() {
+				field.0[Self::index(x, -1)] = *color;
+			}
+		}
+		*self = field;
+	}
+}
+
+/// Чтение чисел из цифр base64
+struct Reader {
+	digits: Vec<u32>,
+	position: usize,
+}
+
+impl Reader {
+	fn new(data: &str) -> Result<Self, String> {
+		let digits = data.chars()
+			.map(|ch| ALPHABET.iter().position(|&digit| digit as char == ch)
+				.map(|value| value as u32)
+				.ok_or_else(|| format!("недопустимый символ '{ch}'")))
+			.collect::<Result<_, _>>()?;
+		Ok(Self { digits, position: 0 })
+	}
+
+	fn is_empty(&self) -> bool {
+		self.position >= self.digits.len()
+	}
+
+	fn poll(&mut self, count: usize) -> Result<u32, String> {
+		let digits = self.digits.get(self.position..self.position + count)
+			.ok_or_else(|| String::from("запись обрывается"))?;
+		self.position += count;
+		Ok(digits.iter().rev().fold(0, |value, digit| value * 64 + digit))
+	}
+}
+
+/// Запись чисел цифрами base64
+#[derive(Default)]
+struct Writer {
+	digits: Vec<u32>,
+}
+
+impl Writer {
+	fn push(&mut self, mut value: u32, count: usize) {
+		for _ in 0..count {
+			self.digits.push(value % 64);
+			value /= 64;
+		}
+	}
+
+	fn into_string(self) -> String {
+		self.digits.iter().map(|&digit| ALPHABET[digit as usize] as char).collect()
+	}
+}
+
+/// Флаги и операция страницы в том виде, в каком они хранятся в записи
+struct Action {
+	operation: Option<FumenOperation>,
+	rise: bool,
+	mirror: bool,
+	colorize: bool,
+	has_comment: bool,
+	lock: bool,
+}
+
+impl Action {
+	fn decode(mut value: u32) -> Self {
+		let mut next = |radix: u32| {
+			let digit = value % radix;
+			value /= radix;
+			digit
+		};
+
+		let piece = next(8);
+		let rotation = FumenRotation::from_code(next(4));
+		let location = next(FIELD_BLOCKS as u32) as i32;
+		let rise = next(2) == 1;
+		let mirror = next(2) == 1;
+		let colorize = next(2) == 1;
+		let has_comment = next(2) == 1;
+		let lock = next(2) == 0;
+
+		let operation = piece_from_code(piece).map(|kind| {
+			let (shift_x, shift_y) = stored_center_shift(kind, rotation);
+			let x = location % FIELD_WIDTH as i32 + shift_x;
+			let y = FIELD_TOP as i32 - 1 - location / FIELD_WIDTH as i32 + shift_y;
+			FumenOperation { kind, rotation, x, y }
+		});
+
+		Self { operation, rise, mirror, colorize, has_comment, lock }
+	}
+
+	fn encode(&self) -> u32 {
+		let (piece, rotation, location) = match &self.operation {
+			Some(operation) => {
+				let (shift_x, shift_y) = stored_center_shift(operation.kind, operation.rotation);
+				let x = operation.x - shift_x;
+				let y = operation.y - shift_y;
+				let location = (FIELD_TOP as i32 - 1 - y) * FIELD_WIDTH as i32 + x;
+				(piece_code(operation.kind) as u32, operation.rotation.code(), location as u32)
+			}
+			// Без фигуры fumen записывает позицию левого верхнего угла
+			None => (0, 0, 0),
+		};
+
+		let flags = [self.rise, self.mirror, self.colorize, self.has_comment, !self.lock];
+		let flags = flags.iter().rev().fold(0, |value, flag| value * 2 + *flag as u32);
+		((flags * FIELD_BLOCKS as u32 + location) * 4 + rotation) * 8 + piece
+	}
+}
+
+/// `escape` из JavaScript, которым fumen кодирует комментарии
+fn escape(text: &str) -> String {
+	const UNESCAPED: &str = "@*_+-./";
+
+	let mut escaped = String::new();
+	for ch in text.chars() {
+		match ch as u32 {
+			_ if ch.is_ascii_alphanumeric() || UNESCAPED.contains(ch) => escaped.push(ch),
+			code @ 0..=0xFF => escaped.push_str(&format!("%{code:02X}")),
+			_ => {
+				let mut units = [0; 2];
+				for unit in ch.encode_utf16(&mut units) {
+					escaped.push_str(&format!("%u{unit:04X}"));
+				}
+			}
+		}
+	}
+	escaped
+}
+
+/// `unescape` из JavaScript
+fn unescape(text: &str) -> String {
+	let chars: Vec<char> = text.chars().collect();
+	let parse_hex = |from: usize, len: usize| -> Option<u16> {
+		let digits: String = chars.get(from..from + len)?.iter().collect();
+		u16::from_str_radix(&digits, 16).ok()
+	};
+
+	let mut units: Vec<u16> = Vec::new();
+	let mut index = 0;
+	while index < chars.len() {
+		let (unit, len) = match chars[index] {
+			'%' if chars.get(index + 1) == Some(&'u') => match parse_hex(index + 2, 4) {
+				Some(unit) => (unit, 6),
+				None => ('%' as u16, 1),
+			},
+			'%' => match parse_hex(index + 1, 2) {
+				Some(unit) => (unit, 3),
+				None => ('%' as u16, 1),
+			},
+			ch => {
+				let mut buffer = [0; 2];
+				units.extend_from_slice(ch.encode_utf16(&mut buffer));
+				index += 1;
+				continue;
+			}
+		};
+		units.push(unit);
+		index += len;
+	}
+	String::from_utf16_lossy(&units)
+}
+
+fn read_comment(reader: &mut Reader) -> Result<String, String> {
+	let table: Vec<char> = COMMENT_TABLE.chars().collect();
+
+	let length = reader.poll(2)? as usize;
+	let mut escaped = String::with_capacity(length);
+	while escaped.len() < length {
+		let mut value = reader.poll(5)?;
+		for _ in 0..4 {
+			escaped.push(table[(value % table.len() as u32) as usize]);
+			value /= table.len() as u32;
+		}
+	}
+	escaped.truncate(length);
+
+	Ok(unescape(&escaped))
+}
+
+fn write_comment(writer: &mut Writer, comment: &str) {
+	let table: Vec<char> = COMMENT_TABLE.chars().collect();
+
+	let escaped: Vec<char> = escape(comment).chars().take(MAX_COMMENT_LENGTH).collect();
+	writer.push(escaped.len() as u32, 2);
+	for chunk in escaped.chunks(4) {
+		let value = chunk.iter().rev().fold(0, |value, ch| {
+			let index = table.iter().position(|c| c == ch).unwrap_or(0);
+			value * table.len() as u32 + index as u32
+		});
+		writer.push(value, 5);
+	}
+}
+
+/// Разбирает запись fumen v115 (https://fumen.zui.jp, можно целиком ссылку) в страницы
+/// с досками размера `BOARD_SIZE`. Запись — последовательность страниц: поле (разностью
+/// с полем после предыдущей страницы), операция с флагами и, возможно, комментарий.
+/// Числа записаны «цифрами» base64, младшей вперёд.
+pub fn decode(text: &str) -> Result<Vec<FumenPage>, String> {
+	let data = match text.find(PREFIX) {
+		Some(start) => &text[start + PREFIX.len()..],
+		None => return Err(String::from("поддерживается только версия v115")),
+	};
+	let data: String = data.chars().filter(|ch| *ch != '?' && !ch.is_whitespace()).collect();
+
+	let mut reader = Reader::new(&data)?;
+	let mut pages = Vec::new();
+	let mut previous = Field::empty();
+	let mut repeat_count = 0;
+	let mut comment: Option<String> = None;
+
+	while !reader.is_empty() {
+		let mut field = previous.clone();
+		if repeat_count == 0 {
+			let mut index = 0;
+			while index < FIELD_BLOCKS {
+				let value = reader.poll(2)?;
+				let diff = value / FIELD_BLOCKS as u32;
+				let count = value as usize % FIELD_BLOCKS + 1;
+				if diff == GRAY_BLOCK as u32 && count == FIELD_BLOCKS {
+					repeat_count = reader.poll(1)? + 1;
+				}
+				if index + count > FIELD_BLOCKS {
+					return Err(String::from("поле длиннее допустимого"));
+				}
+				for block in &mut field.0[index..index + count] {
+					let color = *block as i32 + diff as i32 - GRAY_BLOCK as i32;
+					if !(EMPTY_BLOCK as i32..=GRAY_BLOCK as i32).contains(&color) {
+						return Err(format!("недопустимый цвет клетки {color}"));
+					}
+					*block = color as u8;
+				}
+				index += count;
+			}
+		}
+		repeat_count = repeat_count.saturating_sub(1);
+
+		let action = Action::decode(reader.poll(3)?);
+		if action.has_comment {
+			comment = Some(read_comment(&mut reader)?).filter(|text| !text.is_empty());
+		}
+
+		pages.push(FumenPage {
+			board: field.to_board(BOARD_SIZE)?,
+			operation: action.operation,
+			comment: comment.clone(),
+		});
+
+		if action.lock {
+			field.apply_lock(action.operation.as_ref(), action.rise, action.mirror);
+		}
+		previous = field;
+	}
+
+	if pages.is_empty() {
+		return Err(String::from("пустая запись"));
+	}
+	Ok(pages)
+}
+
+/// Записывает страницы в fumen. Доски шириной 10 и высотой до 23 строк.
+pub fn encode(pages: &[FumenPage]) -> String {
+	const MAX_REPEAT: u32 = 63;
+
+	let mut writer = Writer::default();
+	let mut previous = Field::empty();
+	let mut comment: Option<String> = None;
+	// Позиция счётчика повторов неизменного поля, к которому можно добавить страницу
+	let mut repeat_index: Option<usize> = None;
+
+	for (page_index, page) in pages.iter().enumerate() {
+		let mut field = Field::from_board(&page.board, &previous);
+		// Строка мусора fumen в доске не хранится и переходит как есть
+		for x in 0..FIELD_WIDTH {
+			field.0[Field::index(x, -1)] = previous.0[Field::index(x, -1)];
+		}
+
+		if field != previous {
+			write_field_diff(&mut writer, &previous, &field);
+			repeat_index = None;
+		} else {
+			match repeat_index {
+				Some(index) if writer.digits[index] < MAX_REPEAT => writer.digits[index] += 1,
+				_ => {
+					write_field_diff(&mut writer, &previous, &field);
+					writer.push(0, 1);
+					repeat_index = Some(writer.digits.len() - 1);
+				}
+			}
+		}
+
+		let has_comment = page.comment != comment;
+		let action = Action {
+			operation: page.operation,
+			rise: false,
+			mirror: false,
+			colorize: page_index == 0,
+			has_comment,
+			lock: true,
+		};
+		writer.push(action.encode(), 3);
+		if has_comment {
+			write_comment(&mut writer, page.comment.as_deref().unwrap_or_default());
+			comment = page.comment.clone();
+		}
+
+		field.apply_lock(page.operation.as_ref(), action.rise, action.mirror);
+		previous = field;
+	}
+
+	// Как и fumen, разбиваем длинную запись знаками '?', чтобы её можно было переносить:
+	// первый после 42 символов (с префиксом как раз 47), дальше через каждые 47
+	let data = writer.into_string();
+	if data.len() <= 42 {
+		return format!("{PREFIX}{data}");
+	}
+	let (head, tail) = data.split_at(42);
+	let chunks = tail.as_bytes().chunks(47).map(|chunk| String::from_utf8_lossy(chunk).into_owned());
+	let parts: Vec<String> = iter::once(head.to_string()).chain(chunks).collect();
+	format!("{PREFIX}{}", parts.join("?"))
+}
+
+/// Записывает одну доску без фигур
+pub fn encode_board(board: &Board) -> String {
+	encode(&[FumenPage::new(board.clone())])
+}
+
+fn write_field_diff(writer: &mut Writer, previous: &Field, current: &Field) {
+	let diffs = previous.0.iter().zip(&current.0)
+		.map(|(previous, current)| (*current as i32 - *previous as i32 + GRAY_BLOCK as i32) as u32);
+
+	let mut runs: Vec<(u32, u32)> = Vec::new();
+	for diff in diffs {
+		match runs.last_mut() {
+			Some((last_diff, count)) if *last_diff == diff => *count += 1,
+			_ => runs.push((diff, 1)),
+		}
+	}
+	for (diff, count) in runs {
+		writer.push(diff * FIELD_BLOCKS as u32 + count - 1, 2);
+	}
+}
+
+/// Самая длинная вставляемая запись
+const MAX_INPUT_LENGTH: usize = 8192;
+/// Сколько последних символов вводимой записи видно на экране
+const VISIBLE_INPUT_LENGTH: usize = 50;
+
+/// Экран импорта: вставленная запись fumen открывается в тренировке (дзен без гравитации)
+/// с доской первой страницы
+pub struct FumenImportState {
+	text: String,
+	error_message: Option<String>,
+}
+
+impl FumenImportState {
+	pub fn new() -> Self {
+		Self { text: String::new(), error_message: None }
+	}
+
+	fn start_practice(&mut self) -> NextUpdateAction {
+		match decode(&self.text) {
+			Ok(pages) => {
+				let board = pages.into_iter().next().map(|page| page.board).unwrap_or_else(|| Board::new(BOARD_SIZE));
+				let game = GameState::new(GameMode::Zen { gravity: false }, 0, rand::random()).with_board(board);
+				NextUpdateAction::Switch(Box::new(game))
+			}
+			Err(error) => {
+				self.error_message = Some(format!("Не удалось прочитать запись: {error}"));
+				NextUpdateAction::Continue
+			}
+		}
+	}
+}
+
+impl Default for FumenImportState {
+	fn default() -> Self {
+		Self::new()
+	}
+}
+
+impl State for FumenImportState {
+	fn update(&mut self, _context: &UpdateContext) -> std::io::Result<NextUpdateAction> {
+		for key_event in collect_last_key_events()? {
+			if key_event.is_release() {
+				continue;
+			}
+			if is_interrupt(&key_event) {
+				return Ok(NextUpdateAction::Exit);
+			}
+
+			match key_event.code {
+				KeyCode::Char(ch) if !ch.is_control() && self.text.len() < MAX_INPUT_LENGTH => {
+					self.text.push(ch);
+				}
+				KeyCode::Backspace => { self.text.pop(); }
+				KeyCode::Enter => return Ok(self.start_practice()),
+				KeyCode::Esc => return Ok(NextUpdateAction::Switch(Box::new(MenuState::new()))),
+				_ => {}
+			}
+		}
+
+		Ok(NextUpdateAction::Continue)
+	}
+
+	fn render_frame(&self, frame: &mut Frame) {
+		let visible_start = self.text.chars().count().saturating_sub(VISIBLE_INPUT_LENGTH);
+		let visible_text: String = self.text.chars().skip(visible_start).collect();
+
+		let mut lines = vec![
+			String::from("ИМПОРТ FUMEN"),
+			String::new(),
+			String::from("Вставьте запись (v115@...) или ссылку:"),
+			format!("> {:_<VISIBLE_INPUT_LENGTH$}", visible_text),
+			String::new(),
+		];
+		if let Some(message) = &self.error_message {
+			lines.push(message.clone());
+		}
+		lines.push(String::from("Enter — открыть в тренировке, Esc — в меню"));
+
+		push_lines(frame, &lines);
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	/// Доска размера `BOARD_SIZE`, нижние строки которой заданы текстом `Board::from_ascii`
+	fn board_with_bottom(rows: &[&str]) -> Board {
+		let empty_row = ".".repeat(BOARD_SIZE.width);
+		let text: String = iter::repeat_n(empty_row.as_str(), BOARD_SIZE.height - rows.len())
+			.chain(rows.iter().copied())
+			.map(|row| format!("{row}\n"))
+			.collect();
+		Board::from_ascii(&text).unwrap()
+	}
+
+	fn sorted_cells(operation: &FumenOperation) -> Vec<(i32, i32)> {
+		let mut cells = operation.cells().to_vec();
+		cells.sort();
+		cells
+	}
+
+	#[test]
+	fn decodes_known_records() {
+		let pages = decode("v115@vhAAgH").unwrap();
+		assert_eq!(pages.len(), 1);
+		assert!(!pages[0].board.cells.any());
+		assert_eq!(pages[0].operation, None);
+
+		// Фигуры в левом нижнем углу пустого поля, записанные fumen
+		let known = [
+			("v115@vhAxOJ", FigureKind::I, FumenRotation::Spawn, vec![(0, 0), (1, 0), (2, 0), (3, 0)]),
+			("v115@vhAhOJ", FigureKind::I, FumenRotation::Reverse, vec![(0, 0), (1, 0), (2, 0), (3, 0)]),
+			("v115@vhAZEJ", FigureKind::I, FumenRotation::Left, vec![(0, 0), (0, 1), (0, 2), (0, 3)]),
+			("v115@vhATJJ", FigureKind::O, FumenRotation::Spawn, vec![(0, 0), (0, 1), (1, 0), (1, 1)]),
+			("v115@vhAbJJ", FigureKind::O, FumenRotation::Left, vec![(0, 0), (0, 1), (1, 0), (1, 1)]),
+			("v115@vhA0JJ", FigureKind::Z, FumenRotation::Spawn, vec![(0, 1), (1, 0), (1, 1), (2, 0)]),
+			("v115@vhAvJJ", FigureKind::S, FumenRotation::Right, vec![(0, 1), (0, 2), (1, 0), (1, 1)]),
+		];
+		for (text, kind, rotation, cells) in known {
+			let pages = decode(text).unwrap();
+			let operation = pages[0].operation.unwrap();
+			assert_eq!((operation.kind, operation.rotation), (kind, rotation), "{text}");
+			assert_eq!(sorted_cells(&operation), cells, "{text}");
+			assert_eq!(encode(&pages), text);
+		}
+	}
+
+	#[test]
+	fn round_trips_every_piece_and_rotation() {
+		let board = board_with_bottom(&["##.#######"]);
+		let kinds = [FigureKind::I, FigureKind::J, FigureKind::L, FigureKind::T, FigureKind::S, FigureKind::Z, FigureKind::O];
+		for kind in kinds {
+			let mut figure = Figure::of_kind(kind);
+			for _ in 0..4 {
+				let position = Point::new(4, 10);
+				let operation = FumenOperation::from_placement(&figure, &position, BOARD_SIZE.height);
+				let page = FumenPage { board: board.clone(), operation: Some(operation), comment: Some(String::from("тест")) };
+
+				let pages = decode(&encode(&[page.clone(), page])).unwrap();
+				assert_eq!(pages.len(), 2);
+				assert_eq!(pages[0].board.to_ascii(), board.to_ascii());
+				assert_eq!(pages[0].operation, Some(operation));
+				assert_eq!(pages[0].comment.as_deref(), Some("тест"));
+
+				let (decoded_figure, decoded_position) = operation.to_placement(BOARD_SIZE.height).unwrap();
+				assert_eq!(decoded_position, position);
+				assert_eq!((decoded_figure.size, decoded_figure.cells), (figure.size, figure.cells));
+
+				figure = figure.rotated(true);
+			}
+		}
+	}
+
+	#[test]
+	fn splits_long_records_after_42_characters() {
+		let data_length = |text: &str| text.len() - PREFIX.len() - text.matches('?').count();
+
+		// Ровно 41 символ: запись не делится
+		let board = board_with_bottom(&["#.#.#.#.#.", "..#.#.#.#."]);
+		let text = encode_board(&board);
+		assert_eq!(data_length(&text), 41);
+		assert!(!text.contains('?'));
+		assert_eq!(decode(&text).unwrap()[0].board.to_ascii(), board.to_ascii());
+
+		// Ровно 42 символа: тоже не делится
+		let board = board_with_bottom(&["#.#.#.....", "...#.#.#.#"]);
+		let pages = [FumenPage::new(Board::new(BOARD_SIZE)), FumenPage::new(Board::new(BOARD_SIZE)), FumenPage::new(board.clone())];
+		let text = encode(&pages);
+		assert_eq!(data_length(&text), 42);
+		assert!(!text.contains('?'));
+		assert_eq!(decode(&text).unwrap()[2].board.to_ascii(), board.to_ascii());
+
+		// Длинная запись: '?' после 42 символов данных и дальше через каждые 47
+		let board = board_with_bottom(&["#.#.#.#.#.", ".#.#.#.#.#"].repeat(3));
+		let text = encode_board(&board);
+		let parts: Vec<&str> = text[PREFIX.len()..].split('?').collect();
+		assert!(parts.len() > 2);
+		assert_eq!(parts[0].len(), 42);
+		assert!(parts[1..parts.len() - 1].iter().all(|part| part.len() == 47));
+		assert_eq!(decode(&text).unwrap()[0].board.to_ascii(), board.to_ascii());
+	}
+}
@@ -0,0 +1,1132 @@
+use std::collections::VecDeque;
+use std::time::Duration;
+use std::iter;
+
+use rand::SeedableRng;
+use rand_chacha::ChaCha8Rng;
+use crossterm::style::Attribute;
+use serde::{Serialize, Deserialize};
+
+use crate::shared::*;
+use crate::input::*;
+use crate::ui::*;
+use crate::board::{ASCII_EMPTY_CELL, ASCII_FILLED_CELL, Board};
+use crate::figure::{Figure, FigureKind};
+use crate::state::*;
+use crate::render::{Frame, Row, Style, styled};
+use crate::spectator::SpectatorFrame;
+use crate::mode::{GameMode, StackVisibility, TopOut};
+use crate::game_over::GameOverState;
+use crate::replay::{Replay, ReplayEvent};
+use crate::savegame;
+use crate::puzzle::{Puzzle, PuzzleGoal};
+use crate::fumen;
+use crate::versus;
+use crate::finesse::{self, FinesseStats};
+
+/// Генератор фигур. ChaCha8 выбран за то, что его последовательность не меняется
+/// между версиями rand, в отличие от StdRng — иначе старые повторы разойдутся.
+pub type GameRng = ChaCha8Rng;
+
+/// Событие, меняющее состояние игры. Игра полностью определяется seed'ом и
+/// последовательностью событий, поэтому по ним её можно воспроизвести.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum GameEvent {
+	Action(PlayerAction),
+	/// Опускание фигуры по таймеру
+	Gravity,
+	/// Подъём строки мусора снизу по таймеру (режим выживания)
+	GarbageRise,
+	/// Мусор от соперника; встаёт в очередь и поднимается при фиксации фигуры без линий
+	Garbage(GarbageAttack),
+}
+
+/// Атака соперника: `lines` строк мусора с дырой в столбце `hole`
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct GarbageAttack {
+	pub lines: u8,
+	pub hole: u8,
+}
+
+/// Итог завершённой игры
+#[derive(Clone)]
+pub struct GameResult {
+	pub mode: GameMode,
+	pub score: u32,
+	pub lines: u16,
+	pub level: u8,
+	pub time: Duration,
+	pub seed: u64,
+	/// Достигнута ли цель режима (например, линии в спринте)
+	pub completed: bool,
+	/// Время на каждые `SPLIT_LINES` линий
+	pub splits: Vec<Duration>,
+	/// Итоги тренировки техники (только в этом режиме)
+	pub finesse: Option<FinesseStats>,
+}
+
+/// Через сколько линий засекается промежуточное время
+pub const SPLIT_LINES: u16 = 10;
+
+pub const BOARD_SIZE: Size = Size::new(10, 20);
+
+/// Когда зафиксированные клетки начинают гаснуть и когда исчезают совсем (`StackVisibility::Fading`)
+const FADE_START: Duration = Duration::from_secs(4);
+const FADE_END: Duration = Duration::from_secs(5);
+
+#[derive(Serialize, Deserialize)]
+pub struct GameState {
+	mode: GameMode,
+	seed: u64,
+	stack_visibility: StackVisibility,
+	rng: GameRng,
+
+	current_figure: Figure,
+	current_position: Point,
+
+	next_figure: Figure,
+	/// Заданная последовательность фигур (головоломки). Если есть, фигуры берутся
+	/// из неё вместо генератора, а `next_figure` не используется.
+	#[serde(default)]
+	piece_queue: Option<VecDeque<FigureKind>>,
+	board: Board,
+
+	start_level: u8,
+	lines_hit: u16,
+	score: u32,
+
+	is_paused: bool,
+	/// Игра закончена: стакан заполнен или цель режима достигнута
+	game_over: bool,
+	goal_reached: bool,
+	splits: Vec<Duration>,
+
+	/// Время по секундомеру, когда фигура в последний раз опускалась
+	last_figure_lowering_time: Duration,
+	/// Время по секундомеру последнего подъёма мусора
+	#[serde(default)]
+	last_garbage_rise_time: Duration,
+	/// Сколько раз доска очищалась вместо проигрыша (`TopOut::ClearBoard`)
+	#[serde(default)]
+	board_resets: u16,
+	/// Самая длинная цепочка убранных линий (каскадная гравитация)
+	#[serde(default)]
+	best_chain: u8,
+	/// Цель головоломки
+	#[serde(default)]
+	puzzle_goal: Option<PuzzleGoal>,
+	/// Было ли последнее успешное движение фигуры поворотом (для T-спина)
+	#[serde(default)]
+	last_move_was_rotation: bool,
+	/// Полученный от соперника мусор, ещё не поднявшийся на доску
+	#[serde(default)]
+	pending_garbage: VecDeque<GarbageAttack>,
+	/// Строки мусора для соперника, ещё не забранные через `take_outgoing_garbage`
+	#[serde(default)]
+	outgoing_garbage: u16,
+	/// Сколько всего строк мусора отправлено сопернику
+	#[serde(default)]
+	garbage_sent: u16,
+	/// Сколько фигур зафиксировано
+	#[serde(default)]
+	pieces_placed: u32,
+	/// Нажатия (сдвиги, повороты, сбросы), сделанные с появления текущей фигуры
+	#[serde(default)]
+	piece_inputs: u16,
+	/// Разбор техники постановок, если режим её проверяет
+	#[serde(default)]
+	finesse: Option<FinesseStats>,
+	stopwatch: Stopwatch,
+
+	/// Все применённые события, из них собирается повтор
+	events: Vec<ReplayEvent>,
+	/// Стартовая доска в виде `Board::to_ascii`, если игра началась не с пустой
+	#[serde(default)]
+	initial_board: Option<String>,
+
+	/// Ошибка сохранения при выходе; повторный выход закрывает игру без сохранения
+	#[serde(skip)]
+	save_error: Option<String>,
+}
+
+impl GameState {
+	pub fn new(mode: GameMode, start_level: u8, seed: u64) -> Self {
+		let mut rng = GameRng::seed_from_u64(seed);
+		let rules = mode.rules();
+		let mut board = Board::new(BOARD_SIZE)
+			.with_cell_scale(rules.cell_scale)
+			.with_clear_gravity(rules.clear_gravity);
+		if let GameMode::Dig { garbage_rows } = mode {
+			board.push_random_garbage_lines(garbage_rows as usize, 1, &mut rng, Duration::ZERO);
+		}
+
+		Self {
+			mode,
+			seed,
+			stack_visibility: StackVisibility::Visible,
+			current_figure: Figure::choose_random(&mut rng),
+			current_position: Point::new(board.logical_size().width / 2, 0),
+
+			next_figure: Figure::choose_random(&mut rng),
+			piece_queue: None,
+			board,
+			rng,
+
+			start_level,
+			lines_hit: 0,
+			score: 0,
+
+			is_paused: false,
+			game_over: false,
+			goal_reached: false,
+			splits: Vec::new(),
+
+			last_figure_lowering_time: Duration::ZERO,
+			last_garbage_rise_time: Duration::ZERO,
+			board_resets: 0,
+			best_chain: 0,
+			puzzle_goal: None,
+			last_move_was_rotation: false,
+			pending_garbage: VecDeque::new(),
+			outgoing_garbage: 0,
+			garbage_sent: 0,
+			pieces_placed: 0,
+			piece_inputs: 0,
+			finesse: matches!(mode, GameMode::Finesse { .. }).then(FinesseStats::default),
+			stopwatch: Stopwatch::start_new(),
+
+			events: Vec::new(),
+			initial_board: None,
+
+			save_error: None,
+		}
+	}
+
+	/// Игра по головоломке: её доска, фигуры и цель
+	pub fn from_puzzle(puzzle: &Puzzle) -> Self {
+		let mut game = Self::new(GameMode::Puzzle, 0, 0);
+		game.board = puzzle.build_board();
+		game.piece_queue = Some(puzzle.pieces.iter().copied().collect());
+		game.puzzle_goal = Some(puzzle.goal);
+		game.spawn_new_figure();
+		game
+	}
+
+	/// Игра с заданной стартовой доской (например, из fumen). Размер доски не меняется.
+	pub fn with_board(mut self, board: Board) -> Self {
+		if board.size == self.board.size {
+			self.initial_board = Some(board.to_ascii());
+			self.board = board
+				.with_cell_scale(self.board.cell_scale())
+				.with_clear_gravity(self.board.clear_gravity());
+		}
+		self
+	}
+
+	pub fn with_stack_visibility(mut self, stack_visibility: StackVisibility) -> Self {
+		self.stack_visibility = stack_visibility;
+		self
+	}
+
+	pub fn result(&self) -> GameResult {
+		GameResult {
+			mode: self.mode,
+			score: self.score,
+			lines: self.lines_hit,
+			level: self.level(),
+			time: self.stopwatch.elapsed(),
+			seed: self.seed,
+			completed: self.goal_reached,
+			splits: self.splits.clone(),
+			finesse: self.finesse.clone(),
+		}
+	}
+
+	/// Повтор сыгранной на данный момент игры
+	pub fn replay(&self) -> Replay {
+		Replay::new(self.result(), self.start_level, self.stack_visibility, self.events.clone())
+			.with_initial_board(self.initial_board.clone())
+	}
+
+	/// Доска вместе с падающей фигурой построчно, в текстовом виде `Board::to_ascii`.
+	/// Клетки, скрытые от игрока видимостью стакана, не показываются и зрителям.
+	pub fn field_rows(&self) -> Vec<String> {
+		let cell_scale = self.board.cell_scale();
+		let show_figure = !self.game_over;
+		self.board.to_ascii()
+			.lines()
+			.enumerate()
+			.map(|(row, line)| line.chars()
+				.enumerate()
+				.map(|(col, cell)| {
+					if show_figure && self.current_figure.covers(row, col, &self.current_position, cell_scale) {
+						ASCII_FILLED_CELL
+					} else if cell == ASCII_FILLED_CELL && !self.is_stack_cell_visible(col, row) {
+						ASCII_EMPTY_CELL
+					} else {
+						cell
+					}
+				})
+				.collect())
+			.collect()
+	}
+
+	/// Видна ли игроку зафиксированная клетка (x, y) при его видимости стакана
+	/// (гаснущая ещё видна); после конца игры видно всё
+	fn is_stack_cell_visible(&self, x: usize, y: usize) -> bool {
+		if self.game_over {
+			return true;
+		}
+		match self.stack_visibility {
+			StackVisibility::Visible => true,
+			StackVisibility::Fading => self.stopwatch.elapsed().saturating_sub(self.board.lock_time(x, y)) < FADE_END,
+			StackVisibility::Invisible => false,
+		}
+	}
+
+	pub fn board(&self) -> &Board {
+		&self.board
+	}
+
+	pub fn current_figure(&self) -> &Figure {
+		&self.current_figure
+	}
+
+	pub fn current_position(&self) -> Point {
+		self.current_position
+	}
+
+	/// Вид следующей фигуры, если он известен
+	pub fn next_figure_kind(&self) -> Option<FigureKind> {
+		self.upcoming_figure().map(|figure| figure.kind)
+	}
+
+	pub fn pieces_placed(&self) -> u32 {
+		self.pieces_placed
+	}
+
+	pub fn is_paused(&self) -> bool {
+		self.is_paused
+	}
+
+	pub fn is_game_over(&self) -> bool {
+		self.game_over
+	}
+
+	pub fn pause(&mut self) {
+		if !self.is_paused {
+			self.toggle_pause();
+		}
+	}
+
+	pub fn toggle_pause(&mut self) {
+		self.is_paused = !self.is_paused;
+
+		match self.is_paused {
+			false => self.stopwatch.start(),
+			true  => self.stopwatch.pause(),
+		}
+	}
+
+	fn figure_lowering_duration(&self) -> Duration {
+		let level = self.level();
+		match level {
+			0..=8 => Duration::from_micros(800_000 - (83_500 * level as u64)),
+			9 => Duration::from_millis(100),
+			10..=12 => Duration::from_millis(83),
+			13..=15 => Duration::from_millis(67),
+			16..=18 => Duration::from_millis(50),
+			19..=28 => Duration::from_millis(33),
+			_ => Duration::from_millis(17)
+		}
+	}
+
+	fn level(&self) -> u8 {
+		(self.start_level as u16 + (self.lines_hit / 10)) as u8
+	}
+
+	/// Начисляет очки за убранные линии. `chain_step` — номер шага цепочки
+	/// каскадной гравитации (с 1), очки за шаг умножаются на него.
+	fn add_score_for_lines(&mut self, lines: u8, chain_step: u8) {
+		let points = match lines {
+			0 => 0,
+			1 => 40,
+			2 => 100,
+			3 => 300,
+			// При каскаде за один шаг может уйти и больше четырёх линий
+			_ => 1200,
+		} * (self.level() as u32 + 1) * chain_step as u32;
+		self.score += points;
+		self.lines_hit += lines as u16;
+	}
+
+	/// Переполнение стакана: по правилам режима либо конец игры, либо очистка доски
+	fn top_out(&mut self) {
+		match self.mode.rules().top_out {
+			TopOut::EndGame => {
+				self.game_over = true;
+				self.stopwatch.pause();
+			}
+			TopOut::ClearBoard => {
+				self.board = Board::new(self.board.size)
+					.with_cell_scale(self.board.cell_scale())
+					.with_clear_gravity(self.board.clear_gravity());
+				self.board_resets += 1;
+			}
+		}
+	}
+
+	/// Пытается заспавнить новую фигуру. Если не получается — переполнение стакана.
+	/// Если закончилась заданная последовательность фигур — конец игры без цели.
+	fn spawn_new_figure(&mut self) {
+		self.current_figure = match &mut self.piece_queue {
+			Some(queue) => match queue.pop_front() {
+				Some(kind) => Figure::of_kind(kind),
+				None => {
+					self.game_over = true;
+					self.stopwatch.pause();
+					return;
+				}
+			},
+			None => std::mem::replace(&mut self.next_figure, Figure::choose_random(&mut self.rng)),
+		};
+		self.last_move_was_rotation = false;
+		self.piece_inputs = 0;
+		self.current_position = self.spawn_position();
+
+		if !self.board.can_place(&self.current_figure, &self.current_position) {
+			self.top_out();
+		}
+	}
+
+	/// Где появляется новая фигура
+	fn spawn_position(&self) -> Point {
+		Point::new(self.board.logical_size().width / 2, 0)
+	}
+
+	/// Сравнивает нажатия для текущей фигуры с кратчайшим путём к её постановке.
+	/// Вызывается до фиксации, пока доска та же, что была при появлении фигуры.
+	fn check_finesse(&mut self) {
+		if self.finesse.is_none() {
+			return;
+		}
+		let check = finesse::check_placement(
+			&self.board,
+			&Figure::of_kind(self.current_figure.kind),
+			self.spawn_position(),
+			&self.current_figure,
+			self.current_position,
+			self.piece_inputs,
+		);
+		if let (Some(stats), Some(check)) = (&mut self.finesse, check) {
+			stats.record(check);
+		}
+	}
+
+	/// Интервал подъёма мусора в режиме выживания, сокращается с уровнем
+	fn garbage_rise_interval(&self) -> Duration {
+		const INITIAL: Duration = Duration::from_millis(8_000);
+		const STEP_PER_LEVEL: Duration = Duration::from_millis(500);
+		const MINIMUM: Duration = Duration::from_millis(1_500);
+
+		INITIAL.saturating_sub(STEP_PER_LEVEL * self.level() as u32).max(MINIMUM)
+	}
+
+	/// Поднимает строку мусора снизу, выталкивая текущую фигуру вверх, если она мешает
+	fn rise_garbage(&mut self) {
+		self.last_garbage_rise_time = self.stopwatch.elapsed();
+
+		if !self.board.push_random_garbage_lines(1, 1, &mut self.rng, self.last_garbage_rise_time) {
+			self.top_out();
+			return;
+		}
+
+		if !self.board.can_place(&self.current_figure, &self.current_position) {
+			let raised = Point::new(self.current_position.x, self.current_position.y.saturating_sub(1));
+			if self.current_position.y > 0 && self.board.can_place(&self.current_figure, &raised) {
+				self.current_position = raised;
+			} else {
+				self.top_out();
+			}
+		}
+	}
+
+	/// Показатели режима для панели слева от доски: подпись и значение
+	fn statistics(&self) -> Vec<(&'static str, String)> {
+		match self.mode {
+			GameMode::Marathon | GameMode::Big => vec![
+				("УРОВЕНЬ:", self.level().to_string()),
+				("ВРЕМЯ:", 	format_time(self.stopwatch.elapsed())),
+				("СЧЁТ:", 	self.score.to_string()),
+			],
+			GameMode::Sprint { line_target } => vec![
+				("ЛИНИИ:", 	format!("{}/{}", self.lines_hit.min(line_target), line_target)),
+				("ВРЕМЯ:", 	format_time_precise(self.stopwatch.elapsed())),
+				("СЧЁТ:", 	self.score.to_string()),
+			],
+			GameMode::Ultra { .. } => {
+				let time_limit = self.mode.time_limit().unwrap_or_default();
+				vec![
+					("ОСТАЛОСЬ:", format_time_precise(time_limit.saturating_sub(self.stopwatch.elapsed()))),
+					("СЧЁТ:", 	self.score.to_string()),
+					("ЛИНИИ:", 	self.lines_hit.to_string()),
+				]
+			}
+			GameMode::Dig { garbage_rows } => vec![
+				("МУСОР:", 	format!("{}/{}", self.board.garbage_height(), garbage_rows)),
+				("ВРЕМЯ:", 	format_time_precise(self.stopwatch.elapsed())),
+				("ЛИНИИ:", 	self.lines_hit.to_string()),
+			],
+			GameMode::Cascade => vec![
+				("УРОВЕНЬ:", self.level().to_string()),
+				("СЧЁТ:", 	self.score.to_string()),
+				("ЛИНИИ:", 	self.lines_hit.to_string()),
+				("ЦЕПОЧКА:", self.best_chain.to_string()),
+			],
+			GameMode::Versus => vec![
+				("ЛИНИИ:", 	self.lines_hit.to_string()),
+				("АТАКА:", 	self.garbage_sent.to_string()),
+				("МУСОР:", 	self.pending_garbage_lines().to_string()),
+			],
+			GameMode::Finesse { pieces } => {
+				let stats = self.finesse.clone().unwrap_or_default();
+				let last = match stats.last_check {
+					Some(check) if check.is_fault() => format!("+{} ({:?})", check.extra_inputs(), check.kind),
+					Some(_) => String::from("ЧИСТО"),
+					None => String::from("-"),
+				};
+				vec![
+					("ФИГУРЫ:", format!("{}/{}", self.pieces_placed, pieces)),
+					("ОШИБКИ:", stats.faults.to_string()),
+					("ПОСЛЕДНЯЯ:", last),
+				]
+			}
+			GameMode::Puzzle => vec![
+				("ЛИНИИ:", 	self.lines_hit.to_string()),
+				("ФИГУРЫ:", self.piece_queue.as_ref().map_or(0, VecDeque::len).to_string()),
+			],
+			GameMode::Zen { .. } => vec![
+				("ЛИНИИ:", 	self.lines_hit.to_string()),
+				("ВРЕМЯ:", 	format_time(self.stopwatch.elapsed())),
+				("СБРОСЫ:", self.board_resets.to_string()),
+			],
+			GameMode::Survival => {
+				let since_rise = self.stopwatch.elapsed().saturating_sub(self.last_garbage_rise_time);
+				vec![
+					("УРОВЕНЬ:", self.level().to_string()),
+					("ВРЕМЯ:", 	format_time(self.stopwatch.elapsed())),
+					("СЧЁТ:", 	self.score.to_string()),
+					("ПОДЪЁМ:", format_time_precise(self.garbage_rise_interval().saturating_sub(since_rise))),
+				]
+			}
+		}
+	}
+
+	/// Следующая фигура, если она известна
+	fn upcoming_figure(&self) -> Option<Figure> {
+		match &self.piece_queue {
+			Some(queue) => queue.front().copied().map(Figure::of_kind),
+			None => Some(self.next_figure.clone()),
+		}
+	}
+
+	/// T-спин: T-фигура зафиксирована сразу после поворота, и заняты хотя бы три
+	/// из четырёх диагональных соседей её центра (стены считаются занятыми)
+	fn is_t_spin(&self) -> bool {
+		let Some(center) = self.current_figure.t_center() else {
+			return false;
+		};
+		if !self.last_move_was_rotation {
+			return false;
+		}
+
+		let x = (self.current_position.x + center.x) as isize;
+		let y = (self.current_position.y + center.y) as isize;
+		let blocked_corners = [(-1, -1), (1, -1), (-1, 1), (1, 1)].iter()
+			.filter(|(dx, dy)| self.board.is_blocked(x + dx, y + dy))
+			.count();
+		blocked_corners >= 3
+	}
+
+	/// Забирает строки мусора, которые нужно отправить сопернику
+	pub fn take_outgoing_garbage(&mut self) -> u16 {
+		std::mem::take(&mut self.outgoing_garbage)
+	}
+
+	/// Сколько строк мусора ждёт подъёма
+	fn pending_garbage_lines(&self) -> u16 {
+		self.pending_garbage.iter().map(|attack| attack.lines as u16).sum()
+	}
+
+	/// Обмен мусором после фиксации фигуры (игра вдвоём): атака сначала гасит
+	/// ожидающий мусор, остаток уходит сопернику. Если линий не убрано, ожидающий
+	/// мусор поднимается на доску.
+	fn exchange_garbage(&mut self, lines: u8, t_spin: bool) {
+		let perfect_clear = lines > 0 && !self.board.cells.any();
+		let mut attack = versus::attack_lines(lines, t_spin, perfect_clear) as u16;
+
+		while attack > 0 {
+			let Some(pending) = self.pending_garbage.front_mut() else {
+				break;
+			};
+			let cancelled = attack.min(pending.lines as u16);
+			attack -= cancelled;
+			pending.lines -= cancelled as u8;
+			if pending.lines == 0 {
+				self.pending_garbage.pop_front();
+			}
+		}
+		self.outgoing_garbage += attack;
+		self.garbage_sent += attack;
+
+		if lines > 0 {
+			return;
+		}
+		let now = self.stopwatch.elapsed();
+		while let Some(pending) = self.pending_garbage.pop_front() {
+			for _ in 0..pending.lines {
+				if !self.board.push_garbage_line(&[pending.hole as usize], now) {
+					self.top_out();
+					return;
+				}
+			}
+		}
+	}
+
+	/// Засекает промежуточное время и проверяет достижение цели режима.
+	/// `t_spin` — была ли только что зафиксированная фигура T-спином.
+	fn check_goal(&mut self, lines_before: u16, t_spin: bool) {
+		match self.mode {
+			GameMode::Sprint { line_target } => {
+				let now = self.stopwatch.elapsed();
+				let last_split = self.lines_hit.min(line_target) / SPLIT_LINES;
+				for _ in (lines_before / SPLIT_LINES)..last_split {
+					self.splits.push(now);
+				}
+
+				if self.lines_hit >= line_target {
+					self.finish_with_goal();
+				}
+			}
+			GameMode::Dig { .. } if self.board.garbage_height() == 0 => {
+				self.finish_with_goal();
+			}
+			GameMode::Finesse { pieces } if self.pieces_placed >= pieces as u32 => {
+				self.finish_with_goal();
+			}
+			GameMode::Puzzle => {
+				let cleared = self.lines_hit - lines_before;
+				let reached = match self.puzzle_goal {
+					Some(PuzzleGoal::ClearLines { lines }) => self.lines_hit >= lines,
+					Some(PuzzleGoal::PerfectClear) => cleared > 0 && !self.board.cells.any(),
+					Some(PuzzleGoal::TSpinDouble) => t_spin && cleared == 2,
+					None => false,
+				};
+				if reached {
+					self.finish_with_goal();
+				}
+			}
+			_ => {}
+		}
+	}
+
+	/// Размещает текущую фигуру на доске, начисляет очки и спавнит новую
+	fn drop_current_figure(&mut self) {
+		let t_spin = self.is_t_spin();
+		self.check_finesse();
+		let chain = self.board.drop_figure(&self.current_figure, &self.current_position, self.stopwatch.elapsed());
+		self.pieces_placed += 1;
+		let lines_before = self.lines_hit;
+		for (step, cleared) in chain.iter().enumerate() {
+			self.add_score_for_lines(*cleared, step as u8 + 1);
+		}
+		self.best_chain = self.best_chain.max(chain.len() as u8);
+		if self.mode == GameMode::Versus {
+			self.exchange_garbage(chain.iter().sum(), t_spin);
+		}
+		self.check_goal(lines_before, t_spin);
+		if self.game_over {
+			return;
+		}
+		self.spawn_new_figure();
+		self.last_figure_lowering_time = self.stopwatch.elapsed(); // сброс таймера для новой фигуры
+	}
+
+	/// Завершает игру с достигнутой целью режима
+	fn finish_with_goal(&mut self) {
+		self.goal_reached = true;
+		self.game_over = true;
+		self.stopwatch.pause();
+	}
+
+	/// Продвигает игровое время (если игра не на паузе и не окончена).
+	/// В режимах с ограничением времени не выходит за предел и завершает игру по его достижении.
+	pub fn advance_time(&mut self, delta: Duration) {
+		let Some(time_limit) = self.mode.time_limit() else {
+			self.stopwatch.advance(delta);
+			return;
+		};
+
+		let remaining = time_limit.saturating_sub(self.stopwatch.elapsed());
+		self.stopwatch.advance(delta.min(remaining));
+		if self.stopwatch.is_running() && self.stopwatch.elapsed() >= time_limit {
+			self.finish_with_goal();
+		}
+	}
+
+	/// Пора ли опустить фигуру по таймеру
+	pub fn is_gravity_due(&self) -> bool {
+		self.mode.rules().gravity && !self.is_paused && !self.game_over
+			&& self.stopwatch.elapsed().saturating_sub(self.last_figure_lowering_time) > self.figure_lowering_duration()
+	}
+
+	/// Пора ли поднять мусор (только в режиме выживания)
+	pub fn is_garbage_rise_due(&self) -> bool {
+		self.mode == GameMode::Survival && !self.is_paused && !self.game_over
+			&& self.stopwatch.elapsed().saturating_sub(self.last_garbage_rise_time) >= self.garbage_rise_interval()
+	}
+
+	/// Применяет событие в текущий момент игрового времени и записывает его в повтор.
+	/// Пауза и выход событиями не являются и здесь игнорируются.
+	pub fn apply_event(&mut self, event: GameEvent) {
+		if self.is_paused || self.game_over {
+			return;
+		}
+
+		let now = self.stopwatch.elapsed();
+		self.events.push(ReplayEvent::new(now, event));
+
+		use PlayerAction::*;
+		if let GameEvent::Action(MoveLeft | MoveRight | MoveDown | Drop | RotateClockwise | RotateCounterClockwise) = event {
+			self.piece_inputs = self.piece_inputs.saturating_add(1);
+		}
+		match event {
+			GameEvent::Action(MoveLeft) if self.current_position.x > 0 => {
+				let new_pos = Point::new(self.current_position.x - 1, self.current_position.y);
+				if self.board.can_place(&self.current_figure, &new_pos) {
+					self.current_position = new_pos;
+					self.last_move_was_rotation = false;
+				}
+			}
+			GameEvent::Action(MoveRight) => {
+				let new_pos = Point::new(self.current_position.x + 1, self.current_position.y);
+				if self.board.can_place(&self.current_figure, &new_pos) {
+					self.current_position = new_pos;
+					self.last_move_was_rotation = false;
+				}
+			}
+			GameEvent::Action(MoveDown) | GameEvent::Gravity => {
+				let new_pos = Point::new(self.current_position.x, self.current_position.y + 1);
+				if self.board.can_place(&self.current_figure, &new_pos) {
+					self.current_position = new_pos;
+					self.last_figure_lowering_time = now;
+					self.last_move_was_rotation = false;
+				} else {
+					self.drop_current_figure();
+				}
+			}
+			GameEvent::Action(Drop) => {
+				let drop_y = self.board.drop_position(&self.current_figure, &self.current_position).y;
+				if drop_y != self.current_position.y {
+					self.last_move_was_rotation = false;
+				}
+				self.current_position.y = drop_y;
+				self.drop_current_figure();
+			}
+			GameEvent::Action(RotateClockwise) => {
+				let rotated = self.current_figure.rotated(true);
+				if self.board.can_place(&rotated, &self.current_position) {
+					self.current_figure = rotated;
+					self.last_move_was_rotation = true;
+				}
+			}
+			GameEvent::Action(RotateCounterClockwise) => {
+				let rotated = self.current_figure.rotated(false);
+				if self.board.can_place(&rotated, &self.current_position) {
+					self.current_figure = rotated;
+					self.last_move_was_rotation = true;
+				}
+			}
+			GameEvent::GarbageRise => self.rise_garbage(),
+			GameEvent::Garbage(attack) => self.pending_garbage.push_back(attack),
+			_ => {}
+		}
+	}
+}
+
+impl State for GameState {
+	fn update(&mut self, context: &UpdateContext) -> std::io::Result<NextUpdateAction> {
+		if self.game_over {
+			let mut last_frame = Frame::new();
+			self.render_frame(&mut last_frame);
+			return Ok(NextUpdateAction::Switch(Box::new(
+				GameOverState::new(self.result(), self.replay(), &fumen::encode_board(&self.board), last_frame)
+			)));
+		}
+
+		self.advance_time(context.delta_time);
+
+		// Обработка ввода
+		let last_released_keys = collect_last_key_events()?;
+		for key_event in last_released_keys.iter() {
+			use PlayerAction::*;
+			let action = PlayerAction::from_key_event(*key_event);
+
+			match action {
+				Exit => {
+					if self.save_error.is_some() {
+						return Ok(NextUpdateAction::Exit);
+					}
+					match savegame::save(self) {
+						Ok(()) => return Ok(NextUpdateAction::Exit),
+						Err(error) => {
+							self.save_error = Some(format!("Не удалось сохранить игру: {error}"));
+							self.pause();
+						}
+					}
+				}
+				TogglePause => self.toggle_pause(),
+				DoNothing | Restart => {}
+				_ => self.apply_event(GameEvent::Action(action)),
+			}
+		}
+
+		// Опускание по времени
+		if self.is_gravity_due() {
+			self.apply_event(GameEvent::Gravity);
+		}
+		if self.is_garbage_rise_due() {
+			self.apply_event(GameEvent::GarbageRise);
+		}
+
+		Ok(NextUpdateAction::Continue)
+	}
+
+	fn render_frame(&self, frame: &mut Frame) {
+		const EMPTY_PIXEL: 		Pixel = [' ', ' '];
+		const FIGURE_CELL:		Pixel = ['[', ']'];
+		const PREVIEW_CELL: 	Pixel = [' ', '*'];
+		const EMPTY_CELL: 		Pixel = [' ', '.'];
+		const FADING_CELL: 		Pixel = [':', ':'];
+		const LEFT_BORDER: 		Pixel = ['<', '!'];
+		const RIGHT_BORDER: 	Pixel = ['!', '>'];
+		const BOTTOM_BORDER: 	Pixel = ['=', '='];
+		const BOTTOM_CLOSING: 	Pixel = ['\\','/'];
+		const BOTTOM_CLOSING_LEFT_BORDER:  Pixel = EMPTY_PIXEL;
+		const BOTTOM_CLOSING_RIGHT_BORDER: Pixel = EMPTY_PIXEL;
+
+		const GAP_BETWEEN_PARTS: usize = 2;
+
+		const PAUSE_LABEL_FILLER: char = '=';
+		const PAUSE_LABEL_OPENING: char = '[';
+		const PAUSE_LABEL_CLOSING: char = ']';
+
+		// Статистическая часть (слева)
+		let statistics_part: Vec<String> = {
+			let label_and_value = self.statistics();
+
+			let max_labels_width = label_and_value.iter()
+				.map(|(label, _)| label.chars().count())
+				.max()
+				.unwrap_or(0);
+			let max_values_width = label_and_value.iter()
+				.map(|(_, value)| value.chars().count())
+				.max()
+				.unwrap_or(0);
+
+			let mut lines = Vec::from_iter(label_and_value.iter()
+				.map(|(label, value)|
+					format!("{:<max_labels_width$} {:<max_values_width$}", label, value)
+				)
+			);
+
+			if let (false, Some(figure)) = (self.is_paused, self.upcoming_figure()) {
+				let next_figure_width = figure.size.width;
+				let mut next_figure_part: Vec<String> = vec![];
+				for row in 0..figure.size.height {
+					let start_index = row * next_figure_width;
+					let cells_row = &figure.cells[start_index..start_index + next_figure_width];
+
+					next_figure_part.push(
+						iter::once([' '; GAP_BETWEEN_PARTS])
+						.chain(
+							cells_row.iter().map(|cell| {
+								if *cell { FIGURE_CELL } else { EMPTY_PIXEL }
+							})
+						)
+						.flatten()
+						.collect::<String>()
+					);
+				}
+
+				let actual_width = lines.required_width();
+				lines.push(String::from_iter(iter::repeat_n(' ', actual_width)));
+
+				for line in next_figure_part.iter() {
+					lines.push(format!("{:^actual_width$}", line));
+				}
+			}
+
+			if !self.splits.is_empty() {
+				lines.push(String::new());
+				for (index, split) in self.splits.iter().enumerate() {
+					let split_lines = (index as u16 + 1) * SPLIT_LINES;
+					lines.push(format!("{split_lines:>3}: {}", format_time_precise(*split)));
+				}
+			}
+
+			lines
+		};
+
+		// Доска (справа) с текущей фигурой и тенью
+		let board_part: Vec<Row> = {
+			let mut lines = vec![];
+			let board_width = self.board.size.width;
+			let cell_scale = self.board.cell_scale();
+			let pause_label_row = (self.board.size.height / 2) - 1;
+
+			// Текущая фигура не показывается на паузе и после финиша (она уже лежит на доске)
+			let show_figure = !self.is_paused && !self.goal_reached;
+
+			// Тень и гаснущие клетки тусклее остальных
+			let faint_style = Style::default().with_attribute(Attribute::Dim);
+
+			// Зафиксированная клетка с учётом видимости стакана; после конца игры видно всё
+			let now = self.stopwatch.elapsed();
+			let stack_cell = |col: usize, row: usize| -> (Pixel, Style) {
+				if self.game_over {
+					return (FIGURE_CELL, Style::default());
+				}
+				let age = now.saturating_sub(self.board.lock_time(col, row));
+				match self.stack_visibility {
+					StackVisibility::Visible => (FIGURE_CELL, Style::default()),
+					StackVisibility::Fading if age < FADE_START => (FIGURE_CELL, Style::default()),
+					StackVisibility::Fading if age < FADE_END => (FADING_CELL, faint_style),
+					StackVisibility::Fading | StackVisibility::Invisible => (EMPTY_CELL, Style::default()),
+				}
+			};
+
+			// Тень (если не пауза)
+			let shadow_pos = if show_figure {
+				self.board.drop_position(&self.current_figure, &self.current_position)
+			} else {
+				self.current_position // не используется
+			};
+
+			for row in 0..self.board.size.height {
+				if self.is_paused && row == pause_label_row {
+					let mut line = String::new();
+					line.push_pixel(LEFT_BORDER);
+
+					let width = board_width * PIXEL_LENGTH;
+					let label = format!("{} ПАУЗА {}", PAUSE_LABEL_OPENING, PAUSE_LABEL_CLOSING);
+					let label_len = label.chars().count();
+
+					let paddings_sum = width.saturating_sub(label_len);
+					let left_padding = paddings_sum / 2;
+					let right_padding = paddings_sum - left_padding;
+
+					for _ in 0..left_padding {
+						line.push(PAUSE_LABEL_FILLER);
+					}
+					line.push_str(&label);
+					for _ in 0..right_padding {
+						line.push(PAUSE_LABEL_FILLER);
+					}
+
+					line.push_pixel(RIGHT_BORDER);
+					lines.push(styled(&line, Style::default()));
+				} else {
+					let mut line = Row::new();
+					line.push_pixel(LEFT_BORDER);
+
+					for col in 0..board_width {
+						let (pixel, style) = if show_figure && self.current_figure.covers(row, col, &self.current_position, cell_scale) {
+							(FIGURE_CELL, Style::default())
+						} else if show_figure && self.current_figure.covers(row, col, &shadow_pos, cell_scale) {
+							(PREVIEW_CELL, faint_style)
+						} else if self.board.cells[row * board_width + col] {
+							stack_cell(col, row)
+						} else {
+							(EMPTY_CELL, Style::default())
+						};
+						line.push_styled_pixel(pixel, style);
+					}
+
+					line.push_pixel(RIGHT_BORDER);
+					lines.push(line);
+				}
+			}
+
+			// Нижняя граница
+			lines.push(styled(
+				&iter::once(LEFT_BORDER)
+					.chain(iter::repeat_n(BOTTOM_BORDER, board_width))
+					.chain(iter::once(RIGHT_BORDER))
+					.flatten()
+					.collect::<String>(),
+				Style::default(),
+			));
+
+			// Замыкающая линия
+			lines.push(styled(
+				&iter::once(BOTTOM_CLOSING_LEFT_BORDER)
+					.chain(iter::repeat_n(BOTTOM_CLOSING, board_width))
+					.chain(iter::once(BOTTOM_CLOSING_RIGHT_BORDER))
+					.flatten()
+					.collect::<String>(),
+				Style::default(),
+			));
+
+			lines
+		};
+
+		push_parts_side_by_side(frame, &Frame::from_lines(&statistics_part), &Frame::from_rows(board_part), GAP_BETWEEN_PARTS);
+
+		if let Some(message) = &self.save_error {
+			frame.push_line(message);
+			frame.push_line("Esc — выйти без сохранения");
+		}
+	}
+
+	fn spectator_frame(&self) -> Option<SpectatorFrame> {
+		Some(SpectatorFrame {
+			mode: self.mode.to_string(),
+			statistics: self.statistics().into_iter()
+				.map(|(label, value)| (String::from(label), value))
+				.collect(),
+			rows: self.field_rows(),
+			next: self.upcoming_figure().map(|figure| figure.kind),
+			is_paused: self.is_paused,
+			is_game_over: self.game_over,
+		})
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::movegen;
+	use crate::render::{Cell, MemoryRenderer, Renderer};
+
+	#[test]
+	fn cascade_chain_steps_multiply_score() {
+		let mut text = String::from("..........\n").repeat(17);
+		text.push_str("#.........\n");
+		text.push_str("#....#####\n");
+		text.push_str(".#########\n");
+		let board = Board::from_ascii(&text).unwrap();
+
+		let mut game = GameState::new(GameMode::Cascade, 0, 0).with_board(board);
+		game.current_figure = Figure::of_kind(FigureKind::I).rotated(true);
+		game.current_position = Point::new(1, 0);
+		game.apply_event(GameEvent::Action(PlayerAction::Drop));
+
+		// Убранная линия роняет висящую клетку в дыру снизу — второй шаг цепочки
+		// считается вдвое дороже первого
+		assert_eq!(game.best_chain, 2);
+		assert_eq!(game.lines_hit, 2);
+		assert_eq!(game.score, 40 + 40 * 2);
+		assert!(!game.board.cells.any());
+	}
+
+	/// Глиф клетки стакана (`col`, `row`) на экране: пиксель — два символа
+	/// справа от левого борта, а стакан — самая правая часть кадра
+	fn board_pixel(renderer: &MemoryRenderer, col: usize, row: usize) -> (String, Style) {
+		let screen = renderer.screen();
+		let left_border = screen.width() - (BOARD_SIZE.width + 2) * PIXEL_LENGTH;
+		let x = left_border + (col + 1) * PIXEL_LENGTH;
+		let cells: Vec<&Cell> = (x..x + PIXEL_LENGTH).map(|x| screen.cell(x, row).unwrap()).collect();
+		(cells.iter().map(|cell| cell.glyph).collect(), cells[0].style)
+	}
+
+	#[test]
+	fn renders_board_and_pause() {
+		let mut game = GameState::new(GameMode::Marathon, 0, 1);
+		let mut renderer = MemoryRenderer::new();
+		let mut frame = Frame::new();
+		game.render_frame(&mut frame);
+		renderer.draw(&frame).unwrap();
+
+		let figure = game.current_figure().clone();
+		let position = game.current_position();
+		let figure_cells = movegen::figure_cells(&figure, position);
+		let shadow_cells = movegen::figure_cells(&figure, game.board().drop_position(&figure, &position));
+		for cell in &figure_cells {
+			assert_eq!(board_pixel(&renderer, cell.x, cell.y), (String::from("[]"), Style::default()));
+		}
+		let faint = Style::default().with_attribute(Attribute::Dim);
+		for cell in &shadow_cells {
+			assert_eq!(board_pixel(&renderer, cell.x, cell.y), (String::from(" *"), faint));
+		}
+		assert_eq!(board_pixel(&renderer, 0, BOARD_SIZE.height - 1).0, " .");
+		// Борта по краям стакана и нижняя граница под ним
+		assert_eq!(board_pixel(&renderer, BOARD_SIZE.width, 0).0, "!>");
+		assert_eq!(board_pixel(&renderer, 0, BOARD_SIZE.height).0, "==");
+
+		// На паузе фигура спрятана, а посередине стакана надпись
+		game.toggle_pause();
+		frame.clear();
+		game.render_frame(&mut frame);
+		renderer.draw(&frame).unwrap();
+		for cell in figure_cells.iter().filter(|cell| cell.y != BOARD_SIZE.height / 2 - 1) {
+			assert_eq!(board_pixel(&renderer, cell.x, cell.y).0, " .");
+		}
+		let label: String = (0..BOARD_SIZE.width)
+			.map(|col| board_pixel(&renderer, col, BOARD_SIZE.height / 2 - 1).0)
+			.collect();
+		assert_eq!(label, "=====[ ПАУЗА ]======");
+	}
+
+	#[test]
+	fn survival_raises_garbage_over_time() {
+		let mut game = GameState::new(GameMode::Survival, 0, 1);
+		game.advance_time(Duration::from_secs(7));
+		assert!(!game.is_garbage_rise_due());
+		game.advance_time(Duration::from_secs(1));
+		assert!(game.is_garbage_rise_due());
+
+		for height in 1..=2 {
+			game.apply_event(GameEvent::GarbageRise);
+			assert_eq!(game.board().garbage_height(), height);
+			assert!(!game.is_garbage_rise_due());
+			game.advance_time(game.garbage_rise_interval());
+		}
+
+		// Отметка времени впереди секундомера (после восстановления) не должна ронять игру
+		game.last_garbage_rise_time = game.stopwatch.elapsed() + Duration::from_secs(60);
+		game.last_figure_lowering_time = game.last_garbage_rise_time;
+		assert!(!game.is_garbage_rise_due());
+		assert!(!game.is_gravity_due());
+		game.statistics();
+	}
+
+	#[test]
+	fn big_single_clears_two_board_rows() {
+		let mut game = (0..)
+			.map(|seed| GameState::new(GameMode::Big, 0, seed))
+			.find(|game| game.current_figure().kind == FigureKind::I)
+			.unwrap();
+		let size = game.board().size;
+		let logical_width = game.board().logical_size().width;
+		assert_eq!(logical_width * 2, size.width);
+
+		// Две настоящие строки — одна логическая линия с колодцем в последнем столбце
+		let mut text = format!("{}\n", ".".repeat(size.width)).repeat(size.height - 2);
+		text.push_str(&format!("{}..\n", "#".repeat(size.width - 2)).repeat(2));
+		game = game.with_board(Board::from_ascii(&text).unwrap());
+
+		for _ in game.current_position().x..logical_width - 1 {
+			game.apply_event(GameEvent::Action(PlayerAction::MoveRight));
+		}
+		game.apply_event(GameEvent::Action(PlayerAction::Drop));
+
+		let result = game.result();
+		assert_eq!(result.lines, 1);
+		assert_eq!(result.score, 40);
+		// Палка в два столбца шириной стоит на дне, опустившись на две строки
+		let column: Vec<bool> = (0..size.height).map(|y| game.board().cells[y * size.width + size.width - 1]).collect();
+		assert_eq!(column.iter().filter(|filled| **filled).count(), 8 - 2);
+		assert!(column[size.height - 1]);
+	}
+}
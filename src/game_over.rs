@@ -0,0 +1,232 @@
+use crossterm::event::KeyCode;
+
+use crate::shared::*;
+use crate::input::*;
+use crate::ui::*;
+use crate::state::*;
+use crate::render::Frame;
+use crate::game::{GameResult, SPLIT_LINES};
+use crate::mode::Ranking;
+use crate::replay::Replay;
+use crate::highscores::{HighScores, HighScoreEntry, HighScoresState, MAX_NAME_LENGTH};
+use crate::menu::MenuState;
+
+/// Экран конца игры: итоги и ввод имени, если результат попал в таблицу рекордов
+pub struct GameOverState {
+	result: GameResult,
+	/// Последний кадр игры, показывается слева от итогов
+	game_frame: Frame,
+	/// Итоговая доска в формате fumen, чтобы ей можно было поделиться
+	board_fumen: String,
+	high_scores: HighScores,
+	/// Место в таблице рекордов, которое займёт результат
+	expected_rank: Option<usize>,
+	/// Вводимое имя; `None`, если результат не попал в таблицу
+	name_entry: Option<String>,
+	error_message: Option<String>,
+}
+
+impl GameOverState {
+	pub fn new(result: GameResult, replay: Replay, board_fumen: &str, last_game_frame: Frame) -> Self {
+		// Если файл рекордов не прочитался, ввод имени не предлагается,
+		// чтобы сохранение не затёрло его пустой таблицей
+		let (high_scores, expected_rank, load_error) = match HighScores::load() {
+			Ok(high_scores) => {
+				let expected_rank = high_scores.rank_for(&result);
+				(high_scores, expected_rank, None)
+			}
+			Err(error) => (HighScores::default(), None, Some(format!("Не удалось загрузить рекорды: {error}"))),
+		};
+		let name_entry = expected_rank.map(|_| high_scores.last_name.clone());
+
+		let error_message = replay.save().err()
+			.map(|error| format!("Не удалось сохранить повтор: {error}"))
+			.or(load_error);
+
+		Self {
+			result,
+			game_frame: last_game_frame,
+			board_fumen: board_fumen.to_string(),
+			high_scores,
+			expected_rank,
+			name_entry,
+			error_message,
+		}
+	}
+
+	/// Сохраняет результат под введённым именем и переходит к таблице рекордов
+	fn submit_name(&mut self, name: String) -> NextUpdateAction {
+		let name = match name.trim() {
+			"" => String::from("???"),
+			name => name.to_string(),
+		};
+
+		self.high_scores.last_name = name.clone();
+		let rank = self.high_scores.insert(self.result.mode, HighScoreEntry::new(name, &self.result));
+
+		match self.high_scores.save() {
+			Ok(()) => NextUpdateAction::Switch(Box::new(HighScoresState::new(self.result.mode, rank))),
+			Err(error) => {
+				self.error_message = Some(format!("Не удалось сохранить: {error}"));
+				NextUpdateAction::Continue
+			}
+		}
+	}
+}
+
+impl State for GameOverState {
+	fn update(&mut self, _context: &UpdateContext) -> std::io::Result<NextUpdateAction> {
+		for key_event in collect_last_key_events()? {
+			if key_event.is_release() {
+				continue;
+			}
+			if is_interrupt(&key_event) {
+				return Ok(NextUpdateAction::Exit);
+			}
+
+			let Some(name) = &mut self.name_entry else {
+				if matches!(key_event.code, KeyCode::Enter | KeyCode::Esc) {
+					return Ok(NextUpdateAction::Switch(Box::new(MenuState::new())));
+				}
+				continue;
+			};
+
+			match key_event.code {
+				KeyCode::Char(ch) if !ch.is_control() && name.chars().count() < MAX_NAME_LENGTH => {
+					name.push(ch);
+				}
+				KeyCode::Backspace => { name.pop(); }
+				KeyCode::Enter => {
+					let name = self.name_entry.take().unwrap_or_default();
+					return Ok(self.submit_name(name));
+				}
+				KeyCode::Esc => {
+					return Ok(NextUpdateAction::Switch(Box::new(MenuState::new())));
+				}
+				_ => {}
+			}
+		}
+
+		Ok(NextUpdateAction::Continue)
+	}
+
+	fn render_frame(&self, frame: &mut Frame) {
+		const GAP_BETWEEN_PARTS: usize = 4;
+
+		let time = match self.result.mode.ranking() {
+			Ranking::ByScore => format_time(self.result.time),
+			Ranking::ByTime => format_time_precise(self.result.time),
+		};
+
+		let mut summary_part = vec![
+			String::from(self.result.mode.finish_title(self.result.completed)),
+			String::new(),
+			format!("РЕЖИМ:   {}", self.result.mode),
+			format!("СЧЁТ:    {}", self.result.score),
+			format!("ЛИНИИ:   {}", self.result.lines),
+			format!("УРОВЕНЬ: {}", self.result.level),
+			format!("ВРЕМЯ:   {}", time),
+			String::new(),
+		];
+
+		if !self.result.splits.is_empty() {
+			for (index, split) in self.result.splits.iter().enumerate() {
+				let split_lines = (index as u16 + 1) * SPLIT_LINES;
+				summary_part.push(format!("{split_lines:>3} ЛИНИЙ: {}", format_time_precise(*split)));
+			}
+			summary_part.push(String::new());
+		}
+
+		if let Some(finesse) = &self.result.finesse {
+			summary_part.push(format!("ОШИБКИ ТЕХНИКИ: {}", finesse.faults));
+			summary_part.extend(finesse.summary_lines());
+			summary_part.push(String::new());
+		}
+
+		// Запись разбита знаками '?' на куски, которые fumen позволяет переносить
+		summary_part.push(String::from("ДОСКА В FUMEN:"));
+		summary_part.extend(self.board_fumen.split('?').map(String::from));
+		summary_part.push(String::new());
+
+		match &self.name_entry {
+			Some(name) => {
+				let label = match (self.result.mode.ranking(), self.expected_rank) {
+					(Ranking::ByTime, Some(0)) => "ЛИЧНЫЙ РЕКОРД! ВАШЕ ИМЯ:",
+					_ => "НОВЫЙ РЕКОРД! ВАШЕ ИМЯ:",
+				};
+				summary_part.push(String::from(label));
+				summary_part.push(format!("> {:_<MAX_NAME_LENGTH$}", name));
+				summary_part.push(String::new());
+				summary_part.push(String::from("Enter — сохранить"));
+				summary_part.push(String::from("Esc — пропустить"));
+			}
+			None => {
+				summary_part.push(String::from("Enter — в меню"));
+			}
+		}
+
+		if let Some(message) = &self.error_message {
+			summary_part.push(String::new());
+			summary_part.push(message.clone());
+		}
+
+		push_parts_side_by_side(frame, &self.game_frame, &Frame::from_lines(&summary_part), GAP_BETWEEN_PARTS);
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::game::GameState;
+	use crate::mode::GameMode;
+	use crate::render::{MemoryRenderer, Renderer};
+
+	/// Экран конца игры без загрузки рекордов и сохранения повтора с диска
+	fn game_over(name_entry: Option<String>) -> GameOverState {
+		let game = GameState::new(GameMode::Marathon, 0, 1);
+		let mut game_frame = Frame::new();
+		game.render_frame(&mut game_frame);
+		GameOverState {
+			result: game.result(),
+			game_frame,
+			board_fumen: String::from("v115@vhAAgH"),
+			high_scores: HighScores::default(),
+			expected_rank: name_entry.as_ref().map(|_| 0),
+			name_entry,
+			error_message: None,
+		}
+	}
+
+	fn render(state: &GameOverState) -> MemoryRenderer {
+		let mut frame = Frame::new();
+		state.render_frame(&mut frame);
+		let mut renderer = MemoryRenderer::new();
+		renderer.draw(&frame).unwrap();
+		renderer
+	}
+
+	#[test]
+	fn renders_summary_next_to_last_game_frame() {
+		let state = game_over(Some(String::from("ИГРОК")));
+		let renderer = render(&state);
+		let screen = renderer.screen().lines();
+
+		assert_eq!(renderer.screen().height(), state.game_frame.height());
+		// Слева последний кадр игры, справа итоги
+		for (line, game_line) in screen.iter().zip(state.game_frame.lines()) {
+			assert!(line.starts_with(game_line.as_str()));
+		}
+		let summary: Vec<&str> = screen.iter().map(|line| line.trim_end()).collect();
+		assert!(summary.iter().any(|line| line.ends_with("СЧЁТ:    0")));
+		assert!(summary.iter().any(|line| line.ends_with("v115@vhAAgH")));
+		assert!(summary.iter().any(|line| line.ends_with("НОВЫЙ РЕКОРД! ВАШЕ ИМЯ:")));
+		assert!(summary.iter().any(|line| line.ends_with(&format!("> {:_<MAX_NAME_LENGTH$}", "ИГРОК"))));
+	}
+
+	#[test]
+	fn offers_menu_without_new_record() {
+		let screen = render(&game_over(None)).screen().lines();
+		assert!(screen.iter().any(|line| line.trim_end().ends_with("Enter — в меню")));
+		assert!(!screen.iter().any(|line| line.contains("ВАШЕ ИМЯ")));
+	}
+}
@@ -0,0 +1,216 @@
+use std::time::Duration;
+
+use serde::{Serialize, Deserialize};
+
+use crate::shared::*;
+use crate::input::PlayerAction;
+use crate::figure::FigureKind;
+use crate::movegen::{self, Placement};
+use crate::mode::GameMode;
+use crate::game::{GameEvent, GameState};
+
+/// Что агент видит после каждого шага
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Observation {
+	/// Занятость клеток доски (логических) по строкам сверху вниз: `board[y][x]`
+	pub board: Vec<Vec<bool>>,
+	pub current: FigureKind,
+	/// Позиция текущей фигуры (левый верхний угол её рамки)
+	pub position: Point,
+	/// Клетки доски, которые занимает текущая фигура в своём повороте
+	pub current_cells: Vec<Point>,
+	pub next: Option<FigureKind>,
+}
+
+/// Чем агент управляет за один шаг
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ActionSpace {
+	/// Одно нажатие: сдвиг, поворот, опускание или сброс. Пауза, выход,
+	/// перезапуск и пустое действие в среде недопустимы.
+	Input,
+	/// Сразу конечная постановка фигуры из `Environment::legal_placements`
+	Placement,
+}
+
+/// Действие агента; должно соответствовать пространству действий среды
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum Action {
+	Input(PlayerAction),
+	/// Номер постановки в `Environment::legal_placements`
+	Placement(usize),
+}
+
+/// Подробности шага, не входящие в награду
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct StepInfo {
+	pub lines_cleared: u16,
+	pub pieces_placed: u32,
+	pub score: u32,
+	/// Действие не подошло к пространству действий, нажатие не управляет фигурой
+	/// или номер постановки вне списка; игра при этом не менялась
+	pub invalid_action: bool,
+}
+
+/// Нажатия, управляющие фигурой; остальными агент мог бы поставить игру на паузу
+/// или перезапустить её в обход `reset`
+fn is_piece_input(action: PlayerAction) -> bool {
+	matches!(action,
+		PlayerAction::MoveLeft | PlayerAction::MoveRight | PlayerAction::MoveDown | PlayerAction::Drop
+		| PlayerAction::RotateClockwise | PlayerAction::RotateCounterClockwise)
+}
+
+/// Среда для обучения агентов в духе gym: `reset` начинает игру, `step`
+/// применяет действие. Ни терминал, ни реальное время не используются — игровое
+/// время идёт только по `with_step_time`.
+pub struct Environment {
+	mode: GameMode,
+	action_space: ActionSpace,
+	step_time: Duration,
+	game: GameState,
+	placements: Vec<Placement>,
+}
+
+impl Environment {
+	pub fn new(action_space: ActionSpace) -> Self {
+		let mode = GameMode::Marathon;
+		Self {
+			mode,
+			action_space,
+			step_time: Duration::ZERO,
+			game: GameState::new(mode, 0, 0),
+			placements: Vec::new(),
+		}
+	}
+
+	/// Режим, правила и подсчёт очков которого используются (по умолчанию марафон)
+	pub fn with_mode(mut self, mode: GameMode) -> Self {
+		self.mode = mode;
+		self
+	}
+
+	/// Сколько игрового времени проходит за шаг: по нему идут ограничение времени
+	/// и подъём мусора, а в пространстве нажатий ещё и гравитация режима.
+	/// По умолчанию ноль — время стоит, фигура падает только по действиям агента.
+	pub fn with_step_time(mut self, step_time: Duration) -> Self {
+		self.step_time = step_time;
+		self
+	}
+
+	pub fn action_space(&self) -> ActionSpace {
+		self.action_space
+	}
+
+	/// Игра, которую ведёт среда (например, чтобы показать её или взять повтор)
+	pub fn game(&self) -> &GameState {
+		&self.game
+	}
+
+	/// Постановки текущей фигуры, из которых выбирает `Action::Placement`
+	pub fn legal_placements(&self) -> &[Placement] {
+		&self.placements
+	}
+
+	pub fn reset(&mut self, seed: u64) -> Observation {
+		self.game = GameState::new(self.mode, 0, seed);
+		self.update_placements();
+		self.observation()
+	}
+
+	/// Применяет действие. Награда — очки, набранные за шаг; игра окончена,
+	/// когда стакан переполнился или достигнута цель режима.
+	pub fn step(&mut self, action: Action) -> (Observation, f64, bool, StepInfo) {
+		let before = self.game.result();
+
+		let mut invalid_action = false;
+		match (self.action_space, action) {
+			(ActionSpace::Input, Action::Input(action)) if is_piece_input(action) => {
+				self.game.apply_event(GameEvent::Action(action));
+			}
+			(ActionSpace::Placement, Action::Placement(index)) => match self.placements.get(index) {
+				Some(placement) => {
+					for action in placement.actions.clone() {
+						self.game.apply_event(GameEvent::Action(action));
+					}
+				}
+				None => invalid_action = true,
+			},
+			_ => invalid_action = true,
+		}
+		if !invalid_action {
+			self.advance_time();
+			// Мусор мог подняться и под текущей фигурой, поэтому список обновляется каждый шаг
+			self.update_placements();
+		}
+
+		let after = self.game.result();
+		let info = StepInfo {
+			lines_cleared: after.lines - before.lines,
+			pieces_placed: self.game.pieces_placed(),
+			score: after.score,
+			invalid_action,
+		};
+		let reward = (after.score - before.score) as f64;
+		(self.observation(), reward, self.game.is_game_over(), info)
+	}
+
+	/// Игровое время шага: ограничение времени режима, подъём мусора и (для
+	/// нажатий) гравитация. Постановка и так заканчивается сбросом, гравитация ей не нужна.
+	fn advance_time(&mut self) {
+		self.game.advance_time(self.step_time);
+		if self.action_space == ActionSpace::Input && self.game.is_gravity_due() {
+			self.game.apply_event(GameEvent::Gravity);
+		}
+		if self.game.is_garbage_rise_due() {
+			self.game.apply_event(GameEvent::GarbageRise);
+		}
+	}
+
+	fn update_placements(&mut self) {
+		self.placements = match (self.action_space, self.game.is_game_over()) {
+			(ActionSpace::Placement, false) => movegen::reachable_placements(
+				self.game.board(),
+				self.game.current_figure(),
+				self.game.current_position(),
+			),
+			_ => Vec::new(),
+		};
+	}
+
+	fn observation(&self) -> Observation {
+		let board = self.game.board();
+		let size = board.logical_size();
+		let figure = self.game.current_figure();
+		Observation {
+			board: (0..size.height)
+				.map(|y| (0..size.width)
+					.map(|x| board.is_blocked(x as isize, y as isize))
+					.collect())
+				.collect(),
+			current: figure.kind,
+			position: self.game.current_position(),
+			current_cells: movegen::figure_cells(figure, self.game.current_position()),
+			next: self.game.next_figure_kind(),
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn rejects_non_piece_inputs() {
+		let mut env = Environment::new(ActionSpace::Input).with_step_time(Duration::from_secs(1));
+		let start = env.reset(3);
+		for action in [PlayerAction::TogglePause, PlayerAction::Exit, PlayerAction::Restart, PlayerAction::DoNothing] {
+			let (observation, reward, done, info) = env.step(Action::Input(action));
+			assert!(info.invalid_action, "{action:?}");
+			assert_eq!((observation.clone(), reward, done), (start.clone(), 0.0, false));
+			assert!(!env.game().is_paused());
+		}
+
+		let (observation, _, _, info) = env.step(Action::Input(PlayerAction::MoveLeft));
+		assert!(!info.invalid_action);
+		assert_ne!(observation.position, start.position);
+	}
+}
@@ -0,0 +1,290 @@
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+use serde::{Serialize, Deserialize};
+
+use crate::shared::*;
+use crate::input::*;
+use crate::ui::*;
+use crate::state::*;
+use crate::render::Frame;
+use crate::storage;
+use crate::game::GameResult;
+use crate::mode::{GameMode, Ranking};
+use crate::menu::MenuState;
+
+const HIGH_SCORES_FILE: &str = "highscores.json";
+pub const MAX_ENTRIES_PER_MODE: usize = 10;
+pub const MAX_NAME_LENGTH: usize = 12;
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct HighScoreEntry {
+	pub name: String,
+	pub score: u32,
+	pub lines: u16,
+	pub level: u8,
+	pub time_ms: u64,
+	/// Unix-время в секундах
+	pub date: u64,
+	pub seed: u64,
+}
+impl HighScoreEntry {
+	pub fn new(name: String, result: &GameResult) -> Self {
+		Self {
+			name,
+			score: result.score,
+			lines: result.lines,
+			level: result.level,
+			time_ms: result.time.as_millis() as u64,
+			date: unix_time_now().as_secs(),
+			seed: result.seed,
+		}
+	}
+
+	pub fn time(&self) -> Duration {
+		Duration::from_millis(self.time_ms)
+	}
+
+	/// Лучше ли эта запись другой по правилам таблицы режима
+	fn is_better_than(&self, other: &Self, ranking: Ranking) -> bool {
+		use std::cmp::Reverse;
+		match ranking {
+			Ranking::ByScore => (self.score, Reverse(self.time_ms)) > (other.score, Reverse(other.time_ms)),
+			Ranking::ByTime => (Reverse(self.time_ms), self.score) > (Reverse(other.time_ms), other.score),
+		}
+	}
+}
+
+/// Таблицы рекордов по режимам, хранятся на диске
+#[derive(Default, Serialize, Deserialize)]
+pub struct HighScores {
+	/// Таблицы по `GameMode::record_key`
+	tables: BTreeMap<String, Vec<HighScoreEntry>>,
+	/// Последнее введённое имя, подставляется при следующем вводе
+	#[serde(default)]
+	pub last_name: String,
+}
+impl HighScores {
+	/// Загружает таблицы с диска. Отсутствующий файл даёт пустые таблицы,
+	/// повреждённый — ошибку, чтобы следующее сохранение его не затёрло.
+	pub fn load() -> std::io::Result<Self> {
+		storage::load_json(HIGH_SCORES_FILE)
+	}
+
+	pub fn save(&self) -> std::io::Result<()> {
+		storage::save_json(HIGH_SCORES_FILE, self)
+	}
+
+	pub fn entries(&self, mode: GameMode) -> &[HighScoreEntry] {
+		self.tables.get(&mode.record_key()).map(Vec::as_slice).unwrap_or(&[])
+	}
+
+	/// Место (с 0), которое результат займёт в таблице режима, если попадёт в неё
+	pub fn rank_for(&self, result: &GameResult) -> Option<usize> {
+		let ranking = result.mode.ranking();
+		if !result.mode.rules().keeps_records || (ranking == Ranking::ByTime && !result.completed) {
+			return None;
+		}
+
+		let candidate = HighScoreEntry::new(String::new(), result);
+		let entries = self.entries(result.mode);
+		let rank = entries.iter()
+			.position(|other| candidate.is_better_than(other, ranking))
+			.unwrap_or(entries.len());
+
+		(rank < MAX_ENTRIES_PER_MODE).then_some(rank)
+	}
+
+	/// Добавляет запись в таблицу режима. Возвращает её место (с 0), если она попала в таблицу.
+	pub fn insert(&mut self, mode: GameMode, entry: HighScoreEntry) -> Option<usize> {
+		let ranking = mode.ranking();
+		let entries = self.tables.entry(mode.record_key()).or_default();
+		let rank = entries.iter()
+			.position(|other| entry.is_better_than(other, ranking))
+			.unwrap_or(entries.len());
+
+		if rank >= MAX_ENTRIES_PER_MODE {
+			return None;
+		}
+
+		entries.insert(rank, entry);
+		entries.truncate(MAX_ENTRIES_PER_MODE);
+		Some(rank)
+	}
+}
+
+/// Экран просмотра таблиц рекордов
+pub struct HighScoresState {
+	high_scores: HighScores,
+	mode: GameMode,
+	/// Место только что добавленной записи, выделяется в таблице
+	highlighted_rank: Option<usize>,
+	error_message: Option<String>,
+}
+impl HighScoresState {
+	pub fn new(mode: GameMode, highlighted_rank: Option<usize>) -> Self {
+		let (high_scores, error_message) = match HighScores::load() {
+			Ok(high_scores) => (high_scores, None),
+			Err(error) => (HighScores::default(), Some(format!("Не удалось загрузить рекорды: {error}"))),
+		};
+
+		Self {
+			high_scores,
+			mode,
+			highlighted_rank,
+			error_message,
+		}
+	}
+
+	fn switch_mode(&mut self, forward: bool) {
+		let modes = GameMode::all_variants();
+		let index = modes.iter().position(|mode| *mode == self.mode).unwrap_or(0);
+		let new_index = match forward {
+			true => (index + 1) % modes.len(),
+			false => (index + modes.len() - 1) % modes.len(),
+		};
+
+		self.mode = modes[new_index];
+		self.highlighted_rank = None;
+	}
+}
+
+impl State for HighScoresState {
+	fn update(&mut self, _context: &UpdateContext) -> std::io::Result<NextUpdateAction> {
+		for key_event in collect_last_key_events()? {
+			match MenuAction::from_key_event(key_event) {
+				MenuAction::Left => self.switch_mode(false),
+				MenuAction::Right => self.switch_mode(true),
+				MenuAction::Confirm | MenuAction::Back => {
+					return Ok(NextUpdateAction::Switch(Box::new(MenuState::new())));
+				}
+				MenuAction::Exit => return Ok(NextUpdateAction::Exit),
+				_ => {}
+			}
+		}
+
+		Ok(NextUpdateAction::Continue)
+	}
+
+	fn render_frame(&self, frame: &mut Frame) {
+		let mut lines = vec![
+			format!("РЕКОРДЫ: < {:^20} >", self.mode.to_string()),
+			String::new(),
+			format!(
+				"  {:>2}  {:<MAX_NAME_LENGTH$}  {:>8}  {:>5}  {:>3}  {:>9}  {:<10}  {:<16}",
+				"#", "ИМЯ", "СЧЁТ", "ЛИНИИ", "УР.", "ВРЕМЯ", "ДАТА", "SEED",
+			),
+		];
+
+		let entries = self.high_scores.entries(self.mode);
+		for (rank, entry) in entries.iter().enumerate() {
+			let marker = if self.highlighted_rank == Some(rank) { '>' } else { ' ' };
+			let time = match self.mode.ranking() {
+				Ranking::ByScore => format_time(entry.time()),
+				Ranking::ByTime => format_time_precise(entry.time()),
+			};
+			lines.push(format!(
+				"{marker} {:>2}  {:<MAX_NAME_LENGTH$}  {:>8}  {:>5}  {:>3}  {:>9}  {:<10}  {:016x}",
+				rank + 1, entry.name, entry.score, entry.lines, entry.level,
+				time, format_date(entry.date), entry.seed,
+			));
+		}
+		for rank in entries.len()..MAX_ENTRIES_PER_MODE {
+			lines.push(format!("  {:>2}  {:<MAX_NAME_LENGTH$}", rank + 1, "---"));
+		}
+
+		lines.push(String::new());
+		if let Some(message) = &self.error_message {
+			lines.push(message.clone());
+		}
+		lines.push(String::from("←/→ — режим, Enter/Esc — в меню"));
+
+		push_lines(frame, &lines);
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	const SPRINT: GameMode = GameMode::Sprint { line_target: 40 };
+
+	fn result(mode: GameMode, score: u32, time_secs: u64) -> GameResult {
+		GameResult {
+			mode,
+			score,
+			lines: 0,
+			level: 0,
+			time: Duration::from_secs(time_secs),
+			seed: 0,
+			completed: true,
+			splits: Vec::new(),
+			finesse: None,
+		}
+	}
+
+	fn add(high_scores: &mut HighScores, name: &str, result: &GameResult) -> Option<usize> {
+		high_scores.insert(result.mode, HighScoreEntry::new(name.to_string(), result))
+	}
+
+	fn names(high_scores: &HighScores, mode: GameMode) -> Vec<&str> {
+		high_scores.entries(mode).iter().map(|entry| entry.name.as_str()).collect()
+	}
+
+	#[test]
+	fn marathon_ranks_by_score() {
+		let mut high_scores = HighScores::default();
+		assert_eq!(add(&mut high_scores, "середина", &result(GameMode::Marathon, 200, 60)), Some(0));
+		assert_eq!(add(&mut high_scores, "лучший", &result(GameMode::Marathon, 300, 90)), Some(0));
+		assert_eq!(add(&mut high_scores, "худший", &result(GameMode::Marathon, 100, 30)), Some(2));
+		assert_eq!(names(&high_scores, GameMode::Marathon), ["лучший", "середина", "худший"]);
+		assert_eq!(high_scores.rank_for(&result(GameMode::Marathon, 250, 10)), Some(1));
+	}
+
+	#[test]
+	fn sprint_ranks_by_time() {
+		let mut high_scores = HighScores::default();
+		add(&mut high_scores, "минута", &result(SPRINT, 0, 60));
+		add(&mut high_scores, "быстрый", &result(SPRINT, 0, 40));
+		add(&mut high_scores, "средний", &result(SPRINT, 9999, 50));
+		assert_eq!(names(&high_scores, SPRINT), ["быстрый", "средний", "минута"]);
+
+		assert_eq!(high_scores.rank_for(&result(SPRINT, 0, 45)), Some(1));
+		// Недобежавший спринт в таблицу не попадает
+		let unfinished = GameResult { completed: false, ..result(SPRINT, 0, 1) };
+		assert_eq!(high_scores.rank_for(&unfinished), None);
+	}
+
+	#[test]
+	fn ties_keep_earlier_entry_first() {
+		let mut high_scores = HighScores::default();
+		add(&mut high_scores, "первый", &result(GameMode::Marathon, 100, 60));
+		assert_eq!(add(&mut high_scores, "второй", &result(GameMode::Marathon, 100, 60)), Some(1));
+		// При равном счёте выше тот, кто набрал его быстрее
+		assert_eq!(add(&mut high_scores, "быстрый", &result(GameMode::Marathon, 100, 30)), Some(0));
+		assert_eq!(names(&high_scores, GameMode::Marathon), ["быстрый", "первый", "второй"]);
+
+		add(&mut high_scores, "спринт", &result(SPRINT, 0, 40));
+		// При равном времени в спринте выше больший счёт
+		assert_eq!(high_scores.rank_for(&result(SPRINT, 10, 40)), Some(0));
+		assert_eq!(high_scores.rank_for(&result(SPRINT, 0, 40)), Some(1));
+	}
+
+	#[test]
+	fn full_table_drops_the_worst_entry() {
+		let mut high_scores = HighScores::default();
+		for score in 1..=MAX_ENTRIES_PER_MODE as u32 {
+			add(&mut high_scores, &score.to_string(), &result(GameMode::Marathon, score * 10, 60));
+		}
+
+		let too_low = result(GameMode::Marathon, 5, 60);
+		assert_eq!(high_scores.rank_for(&too_low), None);
+		assert_eq!(add(&mut high_scores, "мимо", &too_low), None);
+		assert_eq!(high_scores.entries(GameMode::Marathon).len(), MAX_ENTRIES_PER_MODE);
+
+		assert_eq!(add(&mut high_scores, "рекорд", &result(GameMode::Marathon, 1000, 60)), Some(0));
+		let entries = high_scores.entries(GameMode::Marathon);
+		assert_eq!(entries.len(), MAX_ENTRIES_PER_MODE);
+		assert_eq!(entries.last().unwrap().score, 20);
+	}
+}
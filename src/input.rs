@@ -1,19 +1,146 @@
 use std::collections::VecDeque;
 use std::time::Duration;
 
-use crossterm::event::{self, KeyEvent, Event, poll};
+use serde::{Serialize, Deserialize};
+use crossterm::event::{self, KeyEvent, KeyCode, KeyModifiers, Event, poll};
 
 pub fn collect_last_key_events() -> std::io::Result<Vec<KeyEvent>>{
 	let mut events_buffer: VecDeque<event::KeyEvent> = VecDeque::new();
 
 	while poll(Duration::from_millis(0))? {
-		match event::read()? {
-			Event::Key(key_event) => {
-				events_buffer.push_back(key_event);
-			}
-			_ => {}
+		if let Event::Key(key_event) = event::read()? {
+			events_buffer.push_back(key_event);
 		}
 	}
 
 	Ok(Vec::from(events_buffer))
 }
+
+/// Ctrl+C — выход из программы из любого состояния
+pub fn is_interrupt(event: &KeyEvent) -> bool {
+	event.modifiers.contains(KeyModifiers::CONTROL)
+		&& matches!(event.code, KeyCode::Char('c') | KeyCode::Char('с'))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum PlayerAction {
+	MoveLeft,
+	MoveRight,
+	MoveDown,
+	Drop,
+	RotateClockwise,
+	RotateCounterClockwise,
+	TogglePause,
+	Exit,
+	Restart,
+
+	DoNothing,
+}
+impl PlayerAction {
+	pub fn from_key_event(event: KeyEvent) -> Self {
+		use PlayerAction::*;
+		use KeyCode::*;
+
+		if !event.is_release() {
+			let has_shift = event.modifiers.contains(KeyModifiers::SHIFT);
+			let has_ctrl  = event.modifiers.contains(KeyModifiers::CONTROL);
+
+			match (has_shift, has_ctrl, event.code) {
+				(_, _, Char('a') | Char('ф') | Left)  => return MoveLeft,
+				(_, _, Char('d') | Char('в') | Right) => return MoveRight,
+				(_, _, Char('s') | Char('ы') | Down)  => return MoveDown,
+				(_, _, Char(' '))                     => return Drop,
+				(_, _, Char('q') | Char('й'))         => return RotateClockwise,
+				(_, _, Char('w') | Char('ц') | Up)    => return RotateClockwise,
+				(_, _, Char('e') | Char('у'))         => return RotateCounterClockwise,
+				(_, _, Esc)                           => return Exit,
+				(_, true, Char('c') | Char('с'))      => return Exit,
+				(_, _, Char('p') | Char('з'))         => return TogglePause,
+				(_, _, Char('r') | Char('к'))         => return Restart,
+				_ => {}
+			}
+		}
+
+		PlayerAction::DoNothing
+	}
+}
+
+/// Чьи клавиши при игре вдвоём на одной клавиатуре
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PlayerKeys {
+	/// A/S/D, Q/W/E, пробел
+	Left,
+	/// Стрелки, `.`, Enter
+	Right,
+}
+impl PlayerKeys {
+	/// Действие игрока, если клавиша из его раскладки. Пауза и выход доступны обоим.
+	pub fn action_for(self, event: KeyEvent) -> PlayerAction {
+		use PlayerAction::*;
+		use KeyCode::*;
+
+		if event.is_release() {
+			return DoNothing;
+		}
+		if is_interrupt(&event) {
+			return Exit;
+		}
+
+		match (self, event.code) {
+			(_, Esc) => Exit,
+			(_, Char('p') | Char('з')) => TogglePause,
+
+			(PlayerKeys::Left, Char('a') | Char('ф')) => MoveLeft,
+			(PlayerKeys::Left, Char('d') | Char('в')) => MoveRight,
+			(PlayerKeys::Left, Char('s') | Char('ы')) => MoveDown,
+			(PlayerKeys::Left, Char(' ')) => Drop,
+			(PlayerKeys::Left, Char('q') | Char('й') | Char('w') | Char('ц')) => RotateClockwise,
+			(PlayerKeys::Left, Char('e') | Char('у')) => RotateCounterClockwise,
+
+			(PlayerKeys::Right, Left) => MoveLeft,
+			(PlayerKeys::Right, Right) => MoveRight,
+			(PlayerKeys::Right, Down) => MoveDown,
+			(PlayerKeys::Right, Enter) => Drop,
+			(PlayerKeys::Right, Up) => RotateClockwise,
+			(PlayerKeys::Right, Char('.') | Char('ю')) => RotateCounterClockwise,
+
+			_ => DoNothing,
+		}
+	}
+}
+
+/// Действия в меню и других экранах вне игры
+#[derive(PartialEq)]
+pub enum MenuAction {
+	Up,
+	Down,
+	Left,
+	Right,
+	Confirm,
+	Back,
+	Exit,
+
+	DoNothing,
+}
+impl MenuAction {
+	pub fn from_key_event(event: KeyEvent) -> Self {
+		use KeyCode::*;
+
+		if event.is_release() {
+			return MenuAction::DoNothing;
+		}
+		if is_interrupt(&event) {
+			return MenuAction::Exit;
+		}
+
+		match event.code {
+			Char('w') | Char('ц') | Up    => MenuAction::Up,
+			Char('s') | Char('ы') | Down  => MenuAction::Down,
+			Char('a') | Char('ф') | Left  => MenuAction::Left,
+			Char('d') | Char('в') | Right => MenuAction::Right,
+			Enter | Char(' ')             => MenuAction::Confirm,
+			Esc | Backspace               => MenuAction::Back,
+			_ => MenuAction::DoNothing,
+		}
+	}
+}
@@ -0,0 +1,26 @@
+pub mod shared;
+pub mod input;
+pub mod ui;
+pub mod render;
+pub mod storage;
+pub mod board;
+pub mod figure;
+pub mod state;
+pub mod mode;
+pub mod game;
+pub mod game_over;
+pub mod highscores;
+pub mod replay;
+pub mod savegame;
+pub mod puzzle;
+pub mod fumen;
+pub mod versus;
+pub mod net;
+pub mod netplay;
+pub mod spectator;
+pub mod movegen;
+pub mod bot;
+pub mod finesse;
+pub mod tbp;
+pub mod gym;
+pub mod menu;
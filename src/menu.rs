@@ -0,0 +1,215 @@
+use crate::input::*;
+use crate::ui::*;
+use crate::state::*;
+use crate::render::Frame;
+use crate::game::GameState;
+use crate::mode::{GameMode, StackVisibility};
+use crate::highscores::HighScoresState;
+use crate::replay::ReplayListState;
+use crate::savegame;
+use crate::puzzle::PuzzlePackListState;
+use crate::fumen::FumenImportState;
+use crate::versus::VersusState;
+use crate::netplay::NetLobbyState;
+
+enum MenuItem {
+	Continue,
+	Play(GameMode),
+	Versus,
+	VersusBot,
+	NetVersus,
+	Puzzles,
+	FumenImport,
+	/// Видимость стакана для следующей игры
+	Visibility,
+	HighScores,
+	Replays,
+	Exit,
+}
+impl MenuItem {
+	fn title(&self, visibility: StackVisibility) -> String {
+		match self {
+			MenuItem::Continue => String::from("ПРОДОЛЖИТЬ"),
+			MenuItem::Play(mode) => mode.to_string(),
+			MenuItem::Versus => GameMode::Versus.to_string(),
+			MenuItem::VersusBot => String::from("ПРОТИВ КОМПЬЮТЕРА"),
+			MenuItem::NetVersus => String::from("ИГРА ПО СЕТИ"),
+			MenuItem::Puzzles => String::from("ГОЛОВОЛОМКИ"),
+			MenuItem::FumenImport => String::from("ИМПОРТ FUMEN"),
+			MenuItem::Visibility => format!("ВИДИМОСТЬ: {visibility}"),
+			MenuItem::HighScores => String::from("РЕКОРДЫ"),
+			MenuItem::Replays => String::from("ПОВТОРЫ"),
+			MenuItem::Exit => String::from("ВЫХОД"),
+		}
+	}
+}
+
+/// Главное меню: продолжение сохранённой игры, выбор режима, рекорды, повторы, выход
+pub struct MenuState {
+	items: Vec<MenuItem>,
+	selected: usize,
+	visibility: StackVisibility,
+	error_message: Option<String>,
+}
+
+impl MenuState {
+	pub fn new() -> Self {
+		Self::with_save_present(savegame::exists())
+	}
+
+	/// Меню, где пункт «Продолжить» есть, только если `has_save`
+	fn with_save_present(has_save: bool) -> Self {
+		let continue_item = has_save.then_some(MenuItem::Continue);
+		let items = continue_item.into_iter()
+			.chain(GameMode::defaults().into_iter().map(MenuItem::Play))
+			.chain([MenuItem::Versus, MenuItem::VersusBot, MenuItem::NetVersus, MenuItem::Puzzles, MenuItem::FumenImport, MenuItem::Visibility, MenuItem::HighScores, MenuItem::Replays, MenuItem::Exit])
+			.collect();
+
+		Self { items, selected: 0, visibility: StackVisibility::default(), error_message: None }
+	}
+
+	fn activate_selected(&mut self) -> NextUpdateAction {
+		match self.items[self.selected] {
+			MenuItem::Continue => match savegame::take() {
+				Ok(game) => NextUpdateAction::Switch(Box::new(game)),
+				Err(error) => {
+					self.items.remove(self.selected);
+					self.error_message = Some(format!("Не удалось загрузить игру: {error}"));
+					NextUpdateAction::Continue
+				}
+			}
+			MenuItem::Play(mode) => {
+				let game = GameState::new(mode, 0, rand::random())
+					.with_stack_visibility(self.visibility);
+				NextUpdateAction::Switch(Box::new(game))
+			}
+			MenuItem::Versus => {
+				NextUpdateAction::Switch(Box::new(VersusState::new(rand::random())))
+			}
+			MenuItem::VersusBot => {
+				NextUpdateAction::Switch(Box::new(VersusState::against_bot(rand::random())))
+			}
+			MenuItem::NetVersus => NextUpdateAction::Switch(Box::new(NetLobbyState::new())),
+			MenuItem::Puzzles => {
+				NextUpdateAction::Switch(Box::new(PuzzlePackListState::new()))
+			}
+			MenuItem::FumenImport => {
+				NextUpdateAction::Switch(Box::new(FumenImportState::new()))
+			}
+			MenuItem::Visibility => {
+				// Enter перебирает значения по кругу
+				let next = self.visibility.adjusted(true);
+				self.visibility = if next == self.visibility { StackVisibility::default() } else { next };
+				NextUpdateAction::Continue
+			}
+			MenuItem::HighScores => {
+				NextUpdateAction::Switch(Box::new(HighScoresState::new(GameMode::Marathon, None)))
+			}
+			MenuItem::Replays => {
+				NextUpdateAction::Switch(Box::new(ReplayListState::new()))
+			}
+			MenuItem::Exit => NextUpdateAction::Exit,
+		}
+	}
+}
+
+impl Default for MenuState {
+	fn default() -> Self {
+		Self::new()
+	}
+}
+
+impl State for MenuState {
+	fn update(&mut self, _context: &UpdateContext) -> std::io::Result<NextUpdateAction> {
+		for key_event in collect_last_key_events()? {
+			let action = MenuAction::from_key_event(key_event);
+			match action {
+				MenuAction::Up => {
+					self.selected = (self.selected + self.items.len() - 1) % self.items.len();
+				}
+				MenuAction::Down => {
+					self.selected = (self.selected + 1) % self.items.len();
+				}
+				MenuAction::Left | MenuAction::Right => {
+					let forward = matches!(action, MenuAction::Right);
+					match &mut self.items[self.selected] {
+						MenuItem::Play(mode) => *mode = mode.adjusted(forward),
+						MenuItem::Visibility => self.visibility = self.visibility.adjusted(forward),
+						_ => {}
+					}
+				}
+				MenuAction::Confirm => return Ok(self.activate_selected()),
+				MenuAction::Back | MenuAction::Exit => return Ok(NextUpdateAction::Exit),
+				_ => {}
+			}
+		}
+
+		Ok(NextUpdateAction::Continue)
+	}
+
+	fn render_frame(&self, frame: &mut Frame) {
+		let mut lines = vec![
+			String::from("<! ТЕТРИС !>"),
+			String::new(),
+		];
+
+		for (index, item) in self.items.iter().enumerate() {
+			let is_selected = index == self.selected;
+			let marker = if is_selected { '>' } else { ' ' };
+			match item {
+				MenuItem::Play(mode) if is_selected && mode.has_options() => {
+					lines.push(format!("{marker} < {} >", item.title(self.visibility)));
+				}
+				MenuItem::Visibility if is_selected => {
+					lines.push(format!("{marker} < {} >", item.title(self.visibility)));
+				}
+				_ => lines.push(format!("{marker} {}", item.title(self.visibility))),
+			}
+		}
+
+		lines.push(String::new());
+		if let Some(message) = &self.error_message {
+			lines.push(message.clone());
+		}
+		lines.push(String::from("↑/↓ — выбор, ←/→ — параметры режима"));
+		lines.push(String::from("Enter — ок, Esc — выход"));
+
+		push_lines(frame, &lines);
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::render::{MemoryRenderer, Renderer};
+
+	fn render(menu: &MenuState) -> MemoryRenderer {
+		let mut frame = Frame::new();
+		menu.render_frame(&mut frame);
+		let mut renderer = MemoryRenderer::new();
+		renderer.draw(&frame).unwrap();
+		renderer
+	}
+
+	#[test]
+	fn renders_menu() {
+		let menu = MenuState::with_save_present(false);
+		let renderer = render(&menu);
+
+		let screen = renderer.screen().lines();
+		assert_eq!(renderer.screen().height(), menu.items.len() + 5);
+		assert_eq!(screen[0].trim_end(), "<! ТЕТРИС !>");
+		assert_eq!(screen[2].trim_end(), format!("> {}", GameMode::defaults()[0]));
+		assert_eq!(screen[3].trim_end(), format!("  {}", GameMode::defaults()[1]));
+		assert_eq!(screen.last().unwrap().trim_end(), "Enter — ок, Esc — выход");
+		// Строки дополнены до общей ширины, чтобы перекрыть прошлый кадр
+		assert!(screen.iter().all(|line| line.chars().count() == renderer.screen().width()));
+	}
+
+	#[test]
+	fn offers_continue_with_save() {
+		let screen = render(&MenuState::with_save_present(true)).screen().lines();
+		assert_eq!(screen[2].trim_end(), "> ПРОДОЛЖИТЬ");
+		assert_eq!(screen[3].trim_end(), format!("  {}", GameMode::defaults()[0]));
+	}
+}
@@ -0,0 +1,302 @@
+use std::fmt;
+use std::time::Duration;
+
+use serde::{Serialize, Deserialize};
+
+use crate::shared::format_time;
+use crate::board::ClearGravity;
+
+/// Режим игры вместе с его параметрами
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum GameMode {
+	/// Бесконечная игра до заполнения стакана
+	Marathon,
+	/// Игра на время до заданного количества линий
+	Sprint { line_target: u16 },
+	/// Набрать как можно больше очков за отведённое время
+	Ultra { time_limit_secs: u16 },
+	/// Игра на время: раскопать стартовые строки мусора
+	Dig { garbage_rows: u16 },
+	/// Снизу по таймеру поднимается мусор, с уровнем всё быстрее
+	Survival,
+	/// Тренировка: без проигрыша и рекордов, гравитацию можно отключить
+	Zen { gravity: bool },
+	/// Марафон, в котором каждая клетка фигуры занимает 2×2 клетки доски
+	Big,
+	/// Марафон с каскадной гравитацией: после очистки куски падают и могут
+	/// убрать новые линии, цепочки дают больше очков
+	Cascade,
+	/// Головоломка из файла: доска, фигуры и цель задаются ею самой
+	Puzzle,
+	/// Игра вдвоём: убранные линии отправляют сопернику мусор
+	Versus,
+	/// Тренировка техники: каждая постановка сравнивается с кратчайшей
+	/// последовательностью нажатий, игра заканчивается после `pieces` фигур
+	Finesse { pieces: u16 },
+}
+
+/// Что происходит, когда новой фигуре нет места
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum TopOut {
+	EndGame,
+	/// Доска очищается, игра продолжается
+	ClearBoard,
+}
+
+/// Правила, по которым режим отличается от обычной игры
+pub struct Rules {
+	pub top_out: TopOut,
+	/// Опускается ли фигура сама по таймеру
+	pub gravity: bool,
+	/// Попадают ли результаты в таблицу рекордов
+	pub keeps_records: bool,
+	/// Сколько клеток доски по каждой оси занимает клетка фигуры
+	pub cell_scale: usize,
+	pub clear_gravity: ClearGravity,
+}
+
+/// Как сравниваются результаты в таблице рекордов режима
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Ranking {
+	/// Больше очков — лучше
+	ByScore,
+	/// Меньше времени — лучше, в таблицу попадают только пройденные игры
+	ByTime,
+}
+
+const SPRINT_LINE_TARGETS: [u16; 3] = [20, 40, 100];
+const DEFAULT_SPRINT_LINE_TARGET: u16 = 40;
+const ULTRA_TIME_LIMITS_SECS: [u16; 4] = [60, 120, 180, 300];
+const DEFAULT_ULTRA_TIME_LIMIT_SECS: u16 = 120;
+/// Не больше 16 строк: сверху должно остаться место для спавна фигуры высотой 4
+const DIG_GARBAGE_ROWS: [u16; 3] = [5, 10, 15];
+const DEFAULT_DIG_GARBAGE_ROWS: u16 = 10;
+const FINESSE_PIECES: [u16; 3] = [50, 100, 200];
+const DEFAULT_FINESSE_PIECES: u16 = 100;
+
+/// Выбирает соседнее значение из списка вариантов параметра
+fn step_option<T: Copy + PartialEq>(options: &[T], current: T, forward: bool) -> T {
+	let index = options.iter().position(|option| *option == current).unwrap_or(0);
+	let new_index = match forward {
+		true => (index + 1).min(options.len() - 1),
+		false => index.saturating_sub(1),
+	};
+	options[new_index]
+}
+
+impl GameMode {
+	/// Режимы с параметрами по умолчанию, в порядке показа в меню
+	pub fn defaults() -> Vec<GameMode> {
+		vec![
+			GameMode::Marathon,
+			GameMode::Sprint { line_target: DEFAULT_SPRINT_LINE_TARGET },
+			GameMode::Ultra { time_limit_secs: DEFAULT_ULTRA_TIME_LIMIT_SECS },
+			GameMode::Dig { garbage_rows: DEFAULT_DIG_GARBAGE_ROWS },
+			GameMode::Survival,
+			GameMode::Zen { gravity: true },
+			GameMode::Big,
+			GameMode::Cascade,
+			GameMode::Finesse { pieces: DEFAULT_FINESSE_PIECES },
+		]
+	}
+
+	/// Все варианты режимов, для которых ведутся таблицы рекордов
+	pub fn all_variants() -> Vec<GameMode> {
+		// Дзен рекордов не ведёт
+		let mut variants = vec![GameMode::Marathon];
+		variants.extend(SPRINT_LINE_TARGETS.map(|line_target| GameMode::Sprint { line_target }));
+		variants.extend(ULTRA_TIME_LIMITS_SECS.map(|time_limit_secs| GameMode::Ultra { time_limit_secs }));
+		variants.extend(DIG_GARBAGE_ROWS.map(|garbage_rows| GameMode::Dig { garbage_rows }));
+		variants.push(GameMode::Survival);
+		variants.push(GameMode::Big);
+		variants.push(GameMode::Cascade);
+		variants
+	}
+
+	pub fn rules(&self) -> Rules {
+		match self {
+			GameMode::Zen { gravity } => Rules {
+				top_out: TopOut::ClearBoard,
+				gravity: *gravity,
+				keeps_records: false,
+				cell_scale: 1,
+				clear_gravity: ClearGravity::Naive,
+			},
+			GameMode::Versus => Rules {
+				top_out: TopOut::EndGame,
+				gravity: true,
+				keeps_records: false,
+				cell_scale: 1,
+				clear_gravity: ClearGravity::Naive,
+			},
+			GameMode::Finesse { .. } => Rules {
+				top_out: TopOut::EndGame,
+				gravity: false,
+				keeps_records: false,
+				cell_scale: 1,
+				clear_gravity: ClearGravity::Naive,
+			},
+			GameMode::Puzzle => Rules {
+				top_out: TopOut::EndGame,
+				gravity: false,
+				keeps_records: false,
+				cell_scale: 1,
+				clear_gravity: ClearGravity::Naive,
+			},
+			GameMode::Big => Rules {
+				top_out: TopOut::EndGame,
+				gravity: true,
+				keeps_records: true,
+				cell_scale: 2,
+				clear_gravity: ClearGravity::Naive,
+			},
+			GameMode::Cascade => Rules {
+				top_out: TopOut::EndGame,
+				gravity: true,
+				keeps_records: true,
+				cell_scale: 1,
+				clear_gravity: ClearGravity::Cascade,
+			},
+			_ => Rules {
+				top_out: TopOut::EndGame,
+				gravity: true,
+				keeps_records: true,
+				cell_scale: 1,
+				clear_gravity: ClearGravity::Naive,
+			},
+		}
+	}
+
+	/// Ключ таблицы рекордов. Для каждого значения параметра — своя таблица.
+	pub fn record_key(&self) -> String {
+		match self {
+			GameMode::Marathon => String::from("Marathon"),
+			GameMode::Sprint { line_target } => format!("Sprint{line_target}"),
+			GameMode::Ultra { time_limit_secs } => format!("Ultra{time_limit_secs}"),
+			GameMode::Dig { garbage_rows } => format!("Dig{garbage_rows}"),
+			GameMode::Survival => String::from("Survival"),
+			GameMode::Zen { .. } => String::from("Zen"),
+			GameMode::Big => String::from("Big"),
+			GameMode::Cascade => String::from("Cascade"),
+			GameMode::Puzzle => String::from("Puzzle"),
+			GameMode::Versus => String::from("Versus"),
+			GameMode::Finesse { pieces } => format!("Finesse{pieces}"),
+		}
+	}
+
+	pub fn ranking(&self) -> Ranking {
+		match self {
+			GameMode::Marathon => Ranking::ByScore,
+			GameMode::Sprint { .. } => Ranking::ByTime,
+			GameMode::Ultra { .. } => Ranking::ByScore,
+			GameMode::Dig { .. } => Ranking::ByTime,
+			GameMode::Survival | GameMode::Zen { .. } | GameMode::Big | GameMode::Cascade | GameMode::Puzzle
+				| GameMode::Versus | GameMode::Finesse { .. } => Ranking::ByScore,
+		}
+	}
+
+	/// Ограничение времени игры, если оно есть
+	pub fn time_limit(&self) -> Option<Duration> {
+		match self {
+			GameMode::Ultra { time_limit_secs } => Some(Duration::from_secs(*time_limit_secs as u64)),
+			_ => None,
+		}
+	}
+
+	/// Заголовок экрана конца игры
+	pub fn finish_title(&self, completed: bool) -> &'static str {
+		match (self, completed) {
+			(GameMode::Sprint { .. }, true) => "ФИНИШ!",
+			(GameMode::Sprint { .. }, false) => "ЦЕЛЬ НЕ ДОСТИГНУТА",
+			(GameMode::Ultra { .. }, true) => "ВРЕМЯ ВЫШЛО!",
+			(GameMode::Dig { .. }, true) => "МУСОР РАСЧИЩЕН!",
+			(GameMode::Dig { .. }, false) => "ЦЕЛЬ НЕ ДОСТИГНУТА",
+			(GameMode::Puzzle, true) => "РЕШЕНО!",
+			(GameMode::Puzzle, false) => "НЕ РЕШЕНО",
+			(GameMode::Finesse { .. }, true) => "ТРЕНИРОВКА ЗАВЕРШЕНА",
+			_ => "ИГРА ОКОНЧЕНА",
+		}
+	}
+
+	/// Есть ли у режима параметр, меняемый в меню
+	pub fn has_options(&self) -> bool {
+		!matches!(
+			self,
+			GameMode::Marathon | GameMode::Survival | GameMode::Big | GameMode::Cascade
+				| GameMode::Puzzle | GameMode::Versus
+		)
+	}
+
+	/// Режим с соседним значением параметра
+	pub fn adjusted(&self, forward: bool) -> Self {
+		match *self {
+			GameMode::Marathon | GameMode::Survival | GameMode::Big | GameMode::Cascade
+				| GameMode::Puzzle | GameMode::Versus => *self,
+			GameMode::Sprint { line_target } => GameMode::Sprint {
+				line_target: step_option(&SPRINT_LINE_TARGETS, line_target, forward),
+			},
+			GameMode::Ultra { time_limit_secs } => GameMode::Ultra {
+				time_limit_secs: step_option(&ULTRA_TIME_LIMITS_SECS, time_limit_secs, forward),
+			},
+			GameMode::Dig { garbage_rows } => GameMode::Dig {
+				garbage_rows: step_option(&DIG_GARBAGE_ROWS, garbage_rows, forward),
+			},
+			GameMode::Zen { gravity } => GameMode::Zen {
+				gravity: step_option(&[true, false], gravity, forward),
+			},
+			GameMode::Finesse { pieces } => GameMode::Finesse {
+				pieces: step_option(&FINESSE_PIECES, pieces, forward),
+			},
+		}
+	}
+}
+
+impl fmt::Display for GameMode {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			GameMode::Marathon => write!(f, "МАРАФОН"),
+			GameMode::Sprint { line_target } => write!(f, "СПРИНТ {line_target} ЛИНИЙ"),
+			GameMode::Ultra { time_limit_secs } => {
+				write!(f, "УЛЬТРА {}", format_time(Duration::from_secs(*time_limit_secs as u64)))
+			}
+			GameMode::Dig { garbage_rows } => write!(f, "РАСКОПКИ {garbage_rows} РЯДОВ"),
+			GameMode::Survival => write!(f, "ВЫЖИВАНИЕ"),
+			GameMode::Zen { gravity: true } => write!(f, "ДЗЕН"),
+			GameMode::Zen { gravity: false } => write!(f, "ДЗЕН БЕЗ ГРАВИТАЦИИ"),
+			GameMode::Big => write!(f, "БОЛЬШИЕ ФИГУРЫ"),
+			GameMode::Cascade => write!(f, "КАСКАД"),
+			GameMode::Puzzle => write!(f, "ГОЛОВОЛОМКА"),
+			GameMode::Versus => write!(f, "ИГРА ВДВОЁМ"),
+			GameMode::Finesse { pieces } => write!(f, "ТЕХНИКА {pieces} ФИГУР"),
+		}
+	}
+}
+
+/// Как отображаются зафиксированные на доске клетки. На симуляцию не влияет.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum StackVisibility {
+	#[default]
+	Visible,
+	/// Клетки исчезают через несколько секунд после фиксации
+	Fading,
+	/// Клетки исчезают сразу после фиксации
+	Invisible,
+}
+
+impl StackVisibility {
+	const ALL: [StackVisibility; 3] = [StackVisibility::Visible, StackVisibility::Fading, StackVisibility::Invisible];
+
+	pub fn adjusted(&self, forward: bool) -> Self {
+		step_option(&Self::ALL, *self, forward)
+	}
+}
+
+impl fmt::Display for StackVisibility {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			StackVisibility::Visible => write!(f, "ОБЫЧНАЯ"),
+			StackVisibility::Fading => write!(f, "ЗАТУХАНИЕ"),
+			StackVisibility::Invisible => write!(f, "НЕВИДИМКА"),
+		}
+	}
+}
@@ -0,0 +1,150 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+
+use crate::shared::*;
+use crate::input::PlayerAction;
+use crate::board::Board;
+use crate::figure::Figure;
+
+/// Конечная постановка фигуры и кратчайшая последовательность действий, которая
+/// приводит к ней из начальной позиции (последнее действие — сброс)
+#[derive(Clone)]
+pub struct Placement {
+	pub figure: Figure,
+	pub position: Point,
+	pub actions: Vec<PlayerAction>,
+}
+
+impl Placement {
+	/// Клетки доски (логические), которые займёт фигура
+	pub fn cells(&self) -> Vec<Point> {
+		figure_cells(&self.figure, self.position)
+	}
+}
+
+/// Клетки доски, которые займёт фигура в позиции `position`
+pub fn figure_cells(figure: &Figure, position: Point) -> Vec<Point> {
+	(0..figure.size.height)
+		.flat_map(|y| (0..figure.size.width).map(move |x| Point::new(x, y)))
+		.filter(|cell| figure.cells[cell.y * figure.size.width + cell.x])
+		.map(|cell| Point::new(position.x + cell.x, position.y + cell.y))
+		.collect()
+}
+
+/// Положение фигуры при поиске: номер поворота по часовой и позиция
+type SearchState = (usize, Point);
+
+/// Все постановки фигуры, достижимые из `position` поворотами и сдвигами влево,
+/// вправо и вниз (включая подсовывания под навесы и повороты у самого дна),
+/// с кратчайшим путём к каждой. Постановки, занимающие одни и те же клетки
+/// (симметричные повороты), считаются одной.
+pub fn reachable_placements(board: &Board, figure: &Figure, position: Point) -> Vec<Placement> {
+	const MOVES: [PlayerAction; 5] = [
+		PlayerAction::RotateClockwise,
+		PlayerAction::RotateCounterClockwise,
+		PlayerAction::MoveLeft,
+		PlayerAction::MoveRight,
+		PlayerAction::MoveDown,
+	];
+
+	if !board.can_place(figure, &position) {
+		return Vec::new();
+	}
+
+	let mut rotations = vec![figure.clone()];
+	for _ in 1..4 {
+		rotations.push(rotations.last().unwrap().rotated(true));
+	}
+
+	// Поиск в ширину: первый найденный путь к состоянию самый короткий
+	let start: SearchState = (0, position);
+	let mut paths: HashMap<SearchState, Vec<PlayerAction>> = HashMap::from([(start, Vec::new())]);
+	let mut queue = VecDeque::from([start]);
+	let mut seen_cells = HashSet::new();
+	let mut placements = Vec::new();
+
+	while let Some(state) = queue.pop_front() {
+		let (rotation, position) = state;
+		let path = paths[&state].clone();
+
+		let figure = &rotations[rotation];
+		let landing = board.drop_position(figure, &position);
+		if seen_cells.insert(figure_cells(figure, landing)) {
+			let mut actions = path.clone();
+			actions.push(PlayerAction::Drop);
+			placements.push(Placement { figure: figure.clone(), position: landing, actions });
+		}
+
+		for action in MOVES {
+			let next: SearchState = match action {
+				PlayerAction::RotateClockwise => ((rotation + 1) % 4, position),
+				PlayerAction::RotateCounterClockwise => ((rotation + 3) % 4, position),
+				PlayerAction::MoveLeft if position.x > 0 => (rotation, Point::new(position.x - 1, position.y)),
+				PlayerAction::MoveRight => (rotation, Point::new(position.x + 1, position.y)),
+				PlayerAction::MoveDown => (rotation, Point::new(position.x, position.y + 1)),
+				_ => continue,
+			};
+			if paths.contains_key(&next) || !board.can_place(&rotations[next.0], &next.1) {
+				continue;
+			}
+
+			let mut next_path = path.clone();
+			next_path.push(action);
+			paths.insert(next, next_path);
+			queue.push_back(next);
+		}
+	}
+
+	placements
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::figure::FigureKind;
+
+	const SPAWN: Point = Point::new(5, 0);
+
+	#[test]
+	fn counts_placements_on_empty_board() {
+		let board = Board::new(Size::new(10, 20));
+		// Различные по клеткам постановки: по числу столбцов для каждой формы поворота
+		let expected = [
+			(FigureKind::I, 10 + 7),
+			(FigureKind::O, 9),
+			(FigureKind::T, 8 + 8 + 9 + 9),
+			(FigureKind::J, 8 + 8 + 9 + 9),
+			(FigureKind::L, 8 + 8 + 9 + 9),
+			(FigureKind::S, 8 + 9),
+			(FigureKind::Z, 8 + 9),
+		];
+		for (kind, count) in expected {
+			let placements = reachable_placements(&board, &Figure::of_kind(kind), SPAWN);
+			assert_eq!(placements.len(), count, "{kind:?}");
+			assert!(placements.iter().all(|placement| placement.actions.last() == Some(&PlayerAction::Drop)));
+		}
+	}
+
+	#[test]
+	fn finds_t_spin_under_overhang() {
+		let mut text = String::from("..........\n").repeat(16);
+		text.push_str(".....#####\n");
+		text.push_str("###...####\n");
+		text.push_str("###..#####\n");
+		text.push_str("####.#####\n");
+		let board = Board::from_ascii(&text).unwrap();
+
+		// Т остриём вниз под навесом в (5, 16): сверху не сбросить, можно только
+		// опустить стоймя в колодец и повернуть у дна
+		let target = vec![Point::new(3, 17), Point::new(4, 17), Point::new(5, 17), Point::new(4, 18)];
+		let placements = reachable_placements(&board, &Figure::of_kind(FigureKind::T), SPAWN);
+		let placement = placements.iter()
+			.find(|placement| placement.cells() == target)
+			.expect("постановка под навесом должна находиться");
+
+		let is_rotation = |action: &PlayerAction| matches!(action,
+			PlayerAction::RotateClockwise | PlayerAction::RotateCounterClockwise);
+		let first_down = placement.actions.iter().position(|action| *action == PlayerAction::MoveDown).unwrap();
+		let last_rotation = placement.actions.iter().rposition(is_rotation).unwrap();
+		assert!(first_down < last_rotation, "{:?}", placement.actions);
+	}
+}
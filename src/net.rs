@@ -0,0 +1,173 @@
+use std::io::{self, BufRead, BufReader, ErrorKind, Write};
+use std::marker::PhantomData;
+use std::net::{Shutdown, TcpStream};
+use std::sync::mpsc::{self, Receiver, Sender, TryRecvError};
+use std::thread;
+use std::time::Duration;
+
+use serde::{Serialize, de::DeserializeOwned};
+
+/// Сколько ждать, пока другая сторона примет отправленное, прежде чем счесть связь потерянной
+const WRITE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Почему соединение закрыто
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CloseReason {
+	/// Связь разорвана или другая сторона ушла
+	Disconnected,
+	/// Другая сторона прислала то, что мы не понимаем (другая версия игры или вовсе не она)
+	ProtocolMismatch(String),
+}
+
+/// TCP-соединение, по которому ходят сообщения в JSON, по одному в строке.
+/// Чтение и запись идут в отдельных потоках, чтобы не тормозить игровой цикл.
+pub struct Connection<Incoming, Outgoing> {
+	/// Строки для потока записи. Когда соединение удаляется, поток дописывает
+	/// оставшееся и закрывает сокет.
+	outgoing: Sender<String>,
+	incoming: Receiver<Result<Incoming, String>>,
+	close_reason: Option<CloseReason>,
+	_outgoing: PhantomData<Outgoing>,
+}
+
+impl<Incoming, Outgoing> Connection<Incoming, Outgoing>
+where
+	Incoming: DeserializeOwned + Send + 'static,
+	Outgoing: Serialize,
+{
+	pub fn new(stream: TcpStream) -> io::Result<Self> {
+		stream.set_nonblocking(false)?;
+		stream.set_nodelay(true)?;
+		stream.set_write_timeout(Some(WRITE_TIMEOUT))?;
+
+		let reader = BufReader::new(stream.try_clone()?);
+		let (sender, incoming) = mpsc::channel();
+		// Поток завершается вместе с соединением: по концу потока, ошибке чтения
+		// или непонятному сообщению (о нём сообщается отдельно). Закрытый канал означает разрыв.
+		thread::spawn(move || {
+			for line in reader.lines() {
+				let Ok(line) = line else { break };
+				let message = serde_json::from_str(&line).map_err(|error| error.to_string());
+				let is_mismatch = message.is_err();
+				if sender.send(message).is_err() || is_mismatch {
+					break;
+				}
+			}
+		});
+
+		let mut writer = stream;
+		let (outgoing, lines) = mpsc::channel::<String>();
+		thread::spawn(move || {
+			for line in lines {
+				if writer.write_all(line.as_bytes()).is_err() {
+					break;
+				}
+			}
+			// Поток чтения на этом тоже заканчивается
+			let _ = writer.shutdown(Shutdown::Both);
+		});
+
+		Ok(Self { outgoing, incoming, close_reason: None, _outgoing: PhantomData })
+	}
+
+	/// Ставит сообщение в очередь на отправку, не дожидаясь записи в сокет
+	pub fn send(&mut self, message: &Outgoing) -> io::Result<()> {
+		let mut line = serde_json::to_string(message)?;
+		line.push('\n');
+		// Поток записи завершается только при ошибке записи
+		if self.outgoing.send(line).is_err() {
+			self.close(CloseReason::Disconnected);
+			return Err(io::Error::new(ErrorKind::BrokenPipe, "соединение разорвано"));
+		}
+		Ok(())
+	}
+
+	/// Все пришедшие с прошлого вызова сообщения
+	pub fn receive(&mut self) -> Vec<Incoming> {
+		let mut messages = Vec::new();
+		loop {
+			match self.incoming.try_recv() {
+				Ok(Ok(message)) => messages.push(message),
+				Ok(Err(error)) => self.close(CloseReason::ProtocolMismatch(error)),
+				Err(TryRecvError::Empty) => break,
+				Err(TryRecvError::Disconnected) => {
+					self.close(CloseReason::Disconnected);
+					break;
+				}
+			}
+		}
+		messages
+	}
+
+	/// Запоминает первую причину закрытия: после непонятного сообщения
+	/// поток чтения завершается, но это не обрыв связи
+	fn close(&mut self, reason: CloseReason) {
+		self.close_reason.get_or_insert(reason);
+	}
+
+	/// Соединение разорвано или соперник прислал то, что мы не понимаем
+	pub fn is_closed(&self) -> bool {
+		self.close_reason.is_some()
+	}
+
+	pub fn close_reason(&self) -> Option<&CloseReason> {
+		self.close_reason.as_ref()
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::net::TcpListener;
+	use std::time::Instant;
+
+	use super::*;
+
+	type TestConnection = Connection<String, String>;
+
+	fn connected_pair() -> (TestConnection, TcpStream) {
+		let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+		let client = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+		let (server, _) = listener.accept().unwrap();
+		(Connection::new(server).unwrap(), client)
+	}
+
+	/// Ждёт, пока придёт хоть что-то или соединение закроется
+	fn receive_some(connection: &mut TestConnection) -> Vec<String> {
+		let deadline = Instant::now() + Duration::from_secs(5);
+		loop {
+			let messages = connection.receive();
+			if !messages.is_empty() || connection.is_closed() || Instant::now() > deadline {
+				return messages;
+			}
+			thread::sleep(Duration::from_millis(5));
+		}
+	}
+
+	#[test]
+	fn exchanges_messages() {
+		let (mut host, client) = connected_pair();
+		let mut guest: TestConnection = Connection::new(client).unwrap();
+
+		host.send(&String::from("привет")).unwrap();
+		guest.send(&String::from("и тебе")).unwrap();
+		assert_eq!(receive_some(&mut guest), vec![String::from("привет")]);
+		assert_eq!(receive_some(&mut host), vec![String::from("и тебе")]);
+
+		// Отправленное перед удалением соединения всё равно доходит, после чего связь закрывается
+		guest.send(&String::from("пока")).unwrap();
+		drop(guest);
+		assert_eq!(receive_some(&mut host), vec![String::from("пока")]);
+		receive_some(&mut host);
+		assert_eq!(host.close_reason(), Some(&CloseReason::Disconnected));
+	}
+
+	#[test]
+	fn reports_protocol_mismatch() {
+		let (mut host, mut client) = connected_pair();
+		client.write_all(b"\"understood\"\n{\"unknown\": 1}\n").unwrap();
+
+		assert_eq!(receive_some(&mut host), vec![String::from("understood")]);
+		receive_some(&mut host);
+		assert!(matches!(host.close_reason(), Some(CloseReason::ProtocolMismatch(_))));
+	}
+}
@@ -0,0 +1,503 @@
+use std::io;
+use std::net::{TcpListener, TcpStream, ToSocketAddrs};
+use std::time::Duration;
+
+use crossterm::event::KeyCode;
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+use serde::{Serialize, Deserialize};
+
+use crate::input::*;
+use crate::ui::*;
+use crate::state::*;
+use crate::render::Frame;
+use crate::spectator::SpectatorFrame;
+use crate::mode::GameMode;
+use crate::game::{BOARD_SIZE, GameEvent, GameState, GarbageAttack};
+use crate::net::{CloseReason, Connection};
+use crate::menu::MenuState;
+
+/// Версия сетевого протокола. Меняется при любом несовместимом изменении `NetMessage`.
+pub const PROTOCOL_VERSION: u32 = 1;
+pub const DEFAULT_ADDRESS: &str = "127.0.0.1:7878";
+
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(3);
+const MAX_ADDRESS_LENGTH: usize = 64;
+
+/// Сообщения между двумя копиями игры
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub enum NetMessage {
+	/// Первое сообщение с каждой стороны. Сид фигур задаёт создавший игру.
+	Hello { protocol: u32, seed: Option<u64> },
+	/// Строки мусора для получателя; дыру выбирает он сам
+	Garbage { lines: u16 },
+	/// Текущая доска отправителя (см. `GameState::field_rows`) для мини-вида
+	Field { rows: Vec<String>, lines: u16 },
+	/// Отправитель проиграл
+	GameOver,
+}
+
+type NetConnection = Connection<NetMessage, NetMessage>;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum NetRole {
+	Host,
+	Join,
+}
+
+/// Экран подключения: адрес и выбор — создать игру или подключиться
+pub struct NetLobbyState {
+	address: String,
+	role: NetRole,
+	/// Ожидание соперника, если игра создана
+	listener: Option<TcpListener>,
+	error_message: Option<String>,
+}
+
+impl NetLobbyState {
+	pub fn new() -> Self {
+		Self::with_address(String::from(DEFAULT_ADDRESS))
+	}
+
+	fn with_address(address: String) -> Self {
+		Self { address, role: NetRole::Host, listener: None, error_message: None }
+	}
+
+	fn start(&mut self) -> NextUpdateAction {
+		self.error_message = None;
+		let result = match self.role {
+			NetRole::Host => self.listen().map(|_| NextUpdateAction::Continue),
+			NetRole::Join => self.connect().map(|stream| self.start_match(stream)),
+		};
+		result.unwrap_or_else(|error| {
+			self.error_message = Some(format!("Ошибка: {error}"));
+			NextUpdateAction::Continue
+		})
+	}
+
+	fn listen(&mut self) -> io::Result<()> {
+		let listener = TcpListener::bind(self.address.trim())?;
+		listener.set_nonblocking(true)?;
+		self.listener = Some(listener);
+		Ok(())
+	}
+
+	fn connect(&self) -> io::Result<TcpStream> {
+		let address = self.address.trim().to_socket_addrs()?
+			.next()
+			.ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "адрес не найден"))?;
+		TcpStream::connect_timeout(&address, CONNECT_TIMEOUT)
+	}
+
+	fn start_match(&mut self, stream: TcpStream) -> NextUpdateAction {
+		match NetVersusState::new(stream, self.role, self.address.clone()) {
+			Ok(state) => NextUpdateAction::Switch(Box::new(state)),
+			Err(error) => {
+				self.error_message = Some(format!("Ошибка: {error}"));
+				NextUpdateAction::Continue
+			}
+		}
+	}
+}
+
+impl Default for NetLobbyState {
+	fn default() -> Self {
+		Self::new()
+	}
+}
+
+impl State for NetLobbyState {
+	fn update(&mut self, _context: &UpdateContext) -> std::io::Result<NextUpdateAction> {
+		for key_event in collect_last_key_events()? {
+			if key_event.is_release() {
+				continue;
+			}
+			if is_interrupt(&key_event) {
+				return Ok(NextUpdateAction::Exit);
+			}
+
+			if self.listener.is_some() {
+				if key_event.code == KeyCode::Esc {
+					self.listener = None;
+				}
+				continue;
+			}
+
+			match key_event.code {
+				KeyCode::Char(ch) if !ch.is_control() && self.address.len() < MAX_ADDRESS_LENGTH => {
+					self.address.push(ch);
+				}
+				KeyCode::Backspace => { self.address.pop(); }
+				KeyCode::Tab | KeyCode::Left | KeyCode::Right | KeyCode::Up | KeyCode::Down => {
+					self.role = match self.role {
+						NetRole::Host => NetRole::Join,
+						NetRole::Join => NetRole::Host,
+					};
+				}
+				KeyCode::Enter => return Ok(self.start()),
+				KeyCode::Esc => return Ok(NextUpdateAction::Switch(Box::new(MenuState::new()))),
+				_ => {}
+			}
+		}
+
+		if let Some(listener) = &self.listener {
+			match listener.accept() {
+				Ok((stream, _)) => {
+					self.listener = None;
+					return Ok(self.start_match(stream));
+				}
+				Err(error) if error.kind() == io::ErrorKind::WouldBlock => {}
+				Err(error) => {
+					self.listener = None;
+					self.error_message = Some(format!("Ошибка: {error}"));
+				}
+			}
+		}
+
+		Ok(NextUpdateAction::Continue)
+	}
+
+	fn render_frame(&self, frame: &mut Frame) {
+		let role_line = match self.role {
+			NetRole::Host => "[СОЗДАТЬ ИГРУ]   подключиться  ",
+			NetRole::Join => " создать игру   [ПОДКЛЮЧИТЬСЯ]",
+		};
+
+		let mut lines = vec![
+			String::from("ИГРА ПО СЕТИ"),
+			String::new(),
+			String::from(role_line),
+			String::new(),
+			String::from("Адрес:"),
+			format!("> {:_<MAX_ADDRESS_LENGTH$}", self.address),
+			String::new(),
+		];
+		if let Some(message) = &self.error_message {
+			lines.push(message.clone());
+		}
+		match self.listener {
+			Some(_) => {
+				lines.push(format!("Ожидание соперника на {}...", self.address.trim()));
+				lines.push(String::from("Esc — отмена"));
+			}
+			None => {
+				lines.push(String::from("Tab — создать/подключиться, Enter — начать, Esc — в меню"));
+			}
+		}
+
+		push_lines(frame, &lines);
+	}
+}
+
+/// Чем закончился сетевой матч
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum NetOutcome {
+	Won,
+	Lost,
+	Disconnected,
+	VersionMismatch { theirs: u32 },
+	/// Соперник прислал сообщение, которое мы не смогли разобрать или которого не ждали
+	ProtocolMismatch,
+}
+
+/// Сетевой матч один на один: своя игра целиком, соперник — мини-видом
+pub struct NetVersusState {
+	connection: NetConnection,
+	role: NetRole,
+	address: String,
+	/// Сид фигур, который создавший игру отправит в приветствии
+	seed: u64,
+	/// Появляется после обмена приветствиями
+	game: Option<GameState>,
+	/// Генератор дыр в полученном мусоре
+	rng: ChaCha8Rng,
+	opponent_rows: Vec<String>,
+	opponent_lines: u16,
+	/// Последняя отправленная доска, чтобы не слать одну и ту же
+	sent_rows: Vec<String>,
+	outcome: Option<NetOutcome>,
+}
+
+impl NetVersusState {
+	fn new(stream: TcpStream, role: NetRole, address: String) -> io::Result<Self> {
+		let seed = rand::random();
+		let mut state = Self {
+			connection: Connection::new(stream)?,
+			role,
+			address,
+			seed,
+			game: None,
+			rng: ChaCha8Rng::seed_from_u64(rand::random()),
+			opponent_rows: Vec::new(),
+			opponent_lines: 0,
+			sent_rows: Vec::new(),
+			outcome: None,
+		};
+
+		let seed = match role {
+			NetRole::Host => Some(seed),
+			NetRole::Join => None,
+		};
+		state.connection.send(&NetMessage::Hello { protocol: PROTOCOL_VERSION, seed })?;
+		Ok(state)
+	}
+
+	fn finish(&mut self, outcome: NetOutcome) {
+		if self.outcome.is_some() {
+			return;
+		}
+		self.outcome = Some(outcome);
+		if let Some(game) = &mut self.game {
+			game.pause();
+		}
+	}
+
+	fn back_to_lobby(&self) -> NextUpdateAction {
+		NextUpdateAction::Switch(Box::new(NetLobbyState::with_address(self.address.clone())))
+	}
+
+	fn handle_message(&mut self, message: NetMessage) {
+		match message {
+			// Приветствие бывает только одно: повтор посреди матча сбросил бы игру
+			NetMessage::Hello { .. } if self.game.is_some() => self.finish(NetOutcome::ProtocolMismatch),
+			NetMessage::Hello { protocol, .. } if protocol != PROTOCOL_VERSION => {
+				self.finish(NetOutcome::VersionMismatch { theirs: protocol });
+			}
+			NetMessage::Hello { seed, .. } => {
+				let seed = match (self.role, seed) {
+					(NetRole::Host, _) => self.seed,
+					(NetRole::Join, Some(seed)) => seed,
+					// Оба создали игру или оба подключились — договориться не выйдет
+					(NetRole::Join, None) => return self.finish(NetOutcome::Disconnected),
+				};
+				self.game = Some(GameState::new(GameMode::Versus, 0, seed));
+			}
+			NetMessage::Garbage { mut lines } => {
+				let Some(game) = &mut self.game else { return };
+				while lines > 0 {
+					let chunk = lines.min(u8::MAX as u16) as u8;
+					lines -= chunk as u16;
+
+					let hole = self.rng.random_range(0..BOARD_SIZE.width) as u8;
+					game.apply_event(GameEvent::Garbage(GarbageAttack { lines: chunk, hole }));
+				}
+			}
+			NetMessage::Field { rows, lines } => {
+				self.opponent_rows = rows;
+				self.opponent_lines = lines;
+			}
+			NetMessage::GameOver => self.finish(NetOutcome::Won),
+		}
+	}
+
+	/// Отправляет сопернику мусор, доску и проигрыш, если они есть
+	fn send_updates(&mut self) -> io::Result<()> {
+		let Some(game) = &mut self.game else { return Ok(()) };
+
+		let garbage = game.take_outgoing_garbage();
+		if garbage > 0 {
+			self.connection.send(&NetMessage::Garbage { lines: garbage })?;
+		}
+
+		let rows = game.field_rows();
+		if rows != self.sent_rows {
+			let lines = game.result().lines;
+			self.connection.send(&NetMessage::Field { rows: rows.clone(), lines })?;
+			self.sent_rows = rows;
+		}
+
+		if game.is_game_over() {
+			self.connection.send(&NetMessage::GameOver)?;
+			self.finish(NetOutcome::Lost);
+		}
+		Ok(())
+	}
+}
+
+impl State for NetVersusState {
+	fn update(&mut self, context: &UpdateContext) -> std::io::Result<NextUpdateAction> {
+		for key_event in collect_last_key_events()? {
+			if key_event.is_release() {
+				continue;
+			}
+			if is_interrupt(&key_event) {
+				return Ok(NextUpdateAction::Exit);
+			}
+
+			if self.outcome.is_some() {
+				match key_event.code {
+					KeyCode::Enter => return Ok(self.back_to_lobby()),
+					KeyCode::Esc => return Ok(NextUpdateAction::Switch(Box::new(MenuState::new()))),
+					_ => {}
+				}
+				continue;
+			}
+
+			use PlayerAction::*;
+			match PlayerAction::from_key_event(key_event) {
+				Exit => {
+					// Уход засчитывается сопернику как победа, а не как обрыв связи
+					let _ = self.connection.send(&NetMessage::GameOver);
+					return Ok(self.back_to_lobby());
+				}
+				// Сетевую игру не остановить и не перезапустить в одиночку
+				TogglePause | Restart | DoNothing => {}
+				action => if let Some(game) = &mut self.game {
+					game.apply_event(GameEvent::Action(action));
+				},
+			}
+		}
+
+		if self.outcome.is_some() {
+			return Ok(NextUpdateAction::Continue);
+		}
+
+		for message in self.connection.receive() {
+			self.handle_message(message);
+		}
+
+		if let Some(game) = &mut self.game && self.outcome.is_none() {
+			game.advance_time(context.delta_time);
+			if game.is_gravity_due() {
+				game.apply_event(GameEvent::Gravity);
+			}
+		}
+
+		match self.connection.close_reason() {
+			Some(CloseReason::ProtocolMismatch(_)) => self.finish(NetOutcome::ProtocolMismatch),
+			Some(CloseReason::Disconnected) => self.finish(NetOutcome::Disconnected),
+			None => {}
+		}
+		if self.outcome.is_none() && self.send_updates().is_err() {
+			self.finish(NetOutcome::Disconnected);
+		}
+
+		Ok(NextUpdateAction::Continue)
+	}
+
+	fn render_frame(&self, frame: &mut Frame) {
+		const GAP_BETWEEN_PARTS: usize = 4;
+
+		let Some(game) = &self.game else {
+			let mut lines = vec![String::from("ИГРА ПО СЕТИ"), String::new()];
+			match self.outcome {
+				Some(outcome) => {
+					lines.push(outcome_message(outcome));
+					lines.push(String::from("Enter — к подключению, Esc — в меню"));
+				}
+				None => {
+					lines.push(String::from("Соединение установлено, ждём соперника..."));
+					lines.push(String::from("Esc — отмена"));
+				}
+			}
+			push_lines(frame, &lines);
+			return;
+		};
+
+		let mut game_part = Frame::new();
+		game.render_frame(&mut game_part);
+
+		let mut opponent_part = vec![
+			String::from("СОПЕРНИК"),
+			format!("ЛИНИИ: {}", self.opponent_lines),
+			String::new(),
+		];
+		opponent_part.extend(self.opponent_rows.iter().map(|row| format!("<!{row}!>")));
+		opponent_part.push(String::new());
+		match self.outcome {
+			Some(outcome) => {
+				opponent_part.push(outcome_message(outcome));
+				opponent_part.push(String::from("Enter — к подключению"));
+				opponent_part.push(String::from("Esc — в меню"));
+			}
+			None => opponent_part.push(String::from("Esc — сдаться")),
+		}
+
+		push_parts_side_by_side(frame, &game_part, &Frame::from_lines(&opponent_part), GAP_BETWEEN_PARTS);
+	}
+
+	fn spectator_frame(&self) -> Option<SpectatorFrame> {
+		self.game.as_ref().and_then(GameState::spectator_frame)
+	}
+}
+
+fn outcome_message(outcome: NetOutcome) -> String {
+	match outcome {
+		NetOutcome::Won => String::from("ПОБЕДА!"),
+		NetOutcome::Lost => String::from("ПОРАЖЕНИЕ"),
+		NetOutcome::Disconnected => String::from("СОЕДИНЕНИЕ ПОТЕРЯНО"),
+		NetOutcome::VersionMismatch { theirs } => {
+			format!("РАЗНЫЕ ВЕРСИИ: у нас {PROTOCOL_VERSION}, у соперника {theirs}")
+		}
+		NetOutcome::ProtocolMismatch => String::from("НЕПОНЯТНОЕ СООБЩЕНИЕ: у соперника другая версия игры?"),
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::io::{BufRead, BufReader, Write};
+	use std::time::Instant;
+
+	use super::*;
+
+	/// Матч, созданный на нашей стороне, и сокет соперника, который пишет в него вручную
+	fn hosted_match() -> (NetVersusState, BufReader<TcpStream>) {
+		let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+		let opponent = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+		let (stream, _) = listener.accept().unwrap();
+		let state = NetVersusState::new(stream, NetRole::Host, String::from(DEFAULT_ADDRESS)).unwrap();
+		opponent.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+		(state, BufReader::new(opponent))
+	}
+
+	fn send_line(opponent: &mut BufReader<TcpStream>, line: &str) {
+		writeln!(opponent.get_mut(), "{line}").unwrap();
+	}
+
+	/// Обрабатывает сообщения соперника, пока не придёт хоть одно
+	fn receive(state: &mut NetVersusState) {
+		let deadline = Instant::now() + Duration::from_secs(5);
+		while Instant::now() < deadline {
+			let messages = state.connection.receive();
+			if !messages.is_empty() {
+				for message in messages {
+					state.handle_message(message);
+				}
+				return;
+			}
+			std::thread::sleep(Duration::from_millis(5));
+		}
+		panic!("соперник ничего не прислал");
+	}
+
+	#[test]
+	fn reports_version_mismatch() {
+		let (mut state, mut opponent) = hosted_match();
+		let mut hello = String::new();
+		opponent.read_line(&mut hello).unwrap();
+		assert!(hello.contains(&format!("\"protocol\":{PROTOCOL_VERSION}")), "{hello}");
+
+		send_line(&mut opponent, r#"{"type":"Hello","protocol":999,"seed":null}"#);
+		receive(&mut state);
+		assert_eq!(state.outcome, Some(NetOutcome::VersionMismatch { theirs: 999 }));
+		assert!(state.game.is_none());
+		assert_eq!(outcome_message(state.outcome.unwrap()), format!("РАЗНЫЕ ВЕРСИИ: у нас {PROTOCOL_VERSION}, у соперника 999"));
+	}
+
+	#[test]
+	fn repeated_hello_is_protocol_error() {
+		let (mut state, mut opponent) = hosted_match();
+		let hello = format!(r#"{{"type":"Hello","protocol":{PROTOCOL_VERSION},"seed":null}}"#);
+		send_line(&mut opponent, &hello);
+		receive(&mut state);
+		let game = state.game.as_mut().unwrap();
+		game.apply_event(GameEvent::Action(PlayerAction::Drop));
+
+		send_line(&mut opponent, &hello);
+		receive(&mut state);
+		assert_eq!(state.outcome, Some(NetOutcome::ProtocolMismatch));
+		// Игра не начата заново
+		assert_eq!(state.game.as_ref().unwrap().pieces_placed(), 1);
+	}
+}
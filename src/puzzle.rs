@@ -0,0 +1,442 @@
+use std::fmt;
+use std::iter;
+
+use crossterm::event::KeyCode;
+use itertools::Itertools;
+use serde::{Serialize, Deserialize};
+
+use crate::shared::*;
+use crate::input::*;
+use crate::ui::*;
+use crate::state::*;
+use crate::render::Frame;
+use crate::spectator::SpectatorFrame;
+use crate::storage;
+use crate::board::{ASCII_EMPTY_CELL, Board};
+use crate::figure::FigureKind;
+use crate::game::{BOARD_SIZE, GameEvent, GameState};
+use crate::menu::MenuState;
+
+const PUZZLES_DIR: &str = "puzzles";
+/// Набор, доступный всегда, даже без файлов в папке данных
+const BUILTIN_PACK: &str = include_str!("../puzzles/basics.json");
+
+/// Что нужно сделать, чтобы решить головоломку
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum PuzzleGoal {
+	/// Убрать не меньше `lines` линий
+	ClearLines { lines: u16 },
+	/// Убрать линии так, чтобы на доске не осталось ни одной клетки
+	PerfectClear,
+	/// Убрать ровно две линии T-фигурой, последним движением которой был поворот
+	TSpinDouble,
+}
+
+impl fmt::Display for PuzzleGoal {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			PuzzleGoal::ClearLines { lines } => write!(f, "УБРАТЬ ЛИНИЙ: {lines}"),
+			PuzzleGoal::PerfectClear => write!(f, "ОЧИСТИТЬ ПОЛЕ"),
+			PuzzleGoal::TSpinDouble => write!(f, "T-SPIN DOUBLE"),
+		}
+	}
+}
+
+/// Головоломка: стартовая доска, заданная последовательность фигур и цель
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Puzzle {
+	pub name: String,
+	/// Нижние строки доски сверху вниз в формате `Board::from_ascii`.
+	/// Строк может быть меньше высоты доски, недостающие сверху пустые.
+	pub board: Vec<String>,
+	/// Фигуры в порядке выдачи; когда они кончаются, головоломка не решена
+	pub pieces: Vec<FigureKind>,
+	pub goal: PuzzleGoal,
+}
+
+impl Puzzle {
+	/// Разбирает доску, дополняя её сверху пустыми строками до полной высоты.
+	/// Номера строк в ошибках — по строкам головоломки.
+	fn parse_board(&self) -> Result<Board, String> {
+		if self.board.len() > BOARD_SIZE.height {
+			return Err(format!("в доске {} строк, максимум {}", self.board.len(), BOARD_SIZE.height));
+		}
+
+		let padding = BOARD_SIZE.height - self.board.len();
+		let empty_row = String::from_iter(iter::repeat_n(ASCII_EMPTY_CELL, BOARD_SIZE.width));
+		let text = iter::repeat_n(&empty_row, padding)
+			.chain(&self.board)
+			.join("\n");
+
+		let board = Board::from_ascii(&text).map_err(|error| {
+			format!("строка {}, столбец {}: {}", error.line.saturating_sub(padding), error.column, error.message)
+		})?;
+		if board.size != BOARD_SIZE {
+			return Err(format!("ширина доски {} вместо {}", board.size.width, BOARD_SIZE.width));
+		}
+
+		Ok(board)
+	}
+
+	/// Проверяет, что доска и фигуры описаны корректно
+	fn validate(&self) -> Result<(), String> {
+		self.parse_board()?;
+
+		if self.pieces.is_empty() {
+			return Err(String::from("не задано ни одной фигуры"));
+		}
+		if self.goal == (PuzzleGoal::ClearLines { lines: 0 }) {
+			return Err(String::from("цель — убрать 0 линий"));
+		}
+
+		Ok(())
+	}
+
+	/// Стартовая доска. Головоломка должна быть проверена через `validate`.
+	pub fn build_board(&self) -> Board {
+		self.parse_board().expect("головоломка проверяется при загрузке")
+	}
+}
+
+/// Набор головоломок, один JSON-файл
+#[derive(Clone, Serialize, Deserialize)]
+pub struct PuzzlePack {
+	pub name: String,
+	pub puzzles: Vec<Puzzle>,
+}
+
+impl PuzzlePack {
+	fn parse(text: &str) -> Result<Self, String> {
+		let pack: PuzzlePack = serde_json::from_str(text).map_err(|error| error.to_string())?;
+		pack.validate()?;
+		Ok(pack)
+	}
+
+	fn validate(&self) -> Result<(), String> {
+		if self.puzzles.is_empty() {
+			return Err(String::from("в наборе нет головоломок"));
+		}
+		for (index, puzzle) in self.puzzles.iter().enumerate() {
+			puzzle.validate()
+				.map_err(|error| format!("головоломка {} «{}»: {error}", index + 1, puzzle.name))?;
+		}
+		Ok(())
+	}
+
+	/// Встроенный набор и наборы из папки `puzzles` в папке данных, по алфавиту.
+	/// Наборы, которые не удалось загрузить, возвращаются как текст ошибки.
+	pub fn load_all() -> Vec<Result<PuzzlePack, String>> {
+		let mut packs = vec![Self::parse(BUILTIN_PACK)];
+
+		match storage::list_json_files(PUZZLES_DIR) {
+			Ok(file_names) => packs.extend(file_names.iter().map(|file_name| {
+				storage::read_json::<PuzzlePack>(file_name)
+					.map_err(|error| error.to_string())
+					.and_then(|pack| pack.validate().map(|()| pack))
+					.map_err(|error| format!("{file_name}: {error}"))
+			})),
+			Err(error) => packs.push(Err(format!("Не удалось прочитать {PUZZLES_DIR}: {error}"))),
+		}
+
+		packs
+	}
+}
+
+/// Экран выбора набора головоломок и головоломки в нём
+pub struct PuzzlePackListState {
+	packs: Vec<Result<PuzzlePack, String>>,
+	/// Выбранная головоломка в каждом наборе
+	puzzle_indices: Vec<usize>,
+	selected: usize,
+}
+
+impl PuzzlePackListState {
+	pub fn new() -> Self {
+		Self::with_selection(0, 0)
+	}
+
+	/// Список с выбранным набором и головоломкой в нём (для возврата из головоломки)
+	fn with_selection(pack_index: usize, puzzle_index: usize) -> Self {
+		let packs = PuzzlePack::load_all();
+		let mut puzzle_indices = vec![0; packs.len()];
+		let selected = pack_index.min(packs.len() - 1);
+		if let Ok(pack) = &packs[selected] {
+			puzzle_indices[selected] = puzzle_index.min(pack.puzzles.len() - 1);
+		}
+
+		Self { packs, puzzle_indices, selected }
+	}
+}
+
+impl Default for PuzzlePackListState {
+	fn default() -> Self {
+		Self::new()
+	}
+}
+
+impl State for PuzzlePackListState {
+	fn update(&mut self, _context: &UpdateContext) -> std::io::Result<NextUpdateAction> {
+		for key_event in collect_last_key_events()? {
+			let action = MenuAction::from_key_event(key_event);
+			match action {
+				MenuAction::Up => {
+					self.selected = (self.selected + self.packs.len() - 1) % self.packs.len();
+				}
+				MenuAction::Down => {
+					self.selected = (self.selected + 1) % self.packs.len();
+				}
+				MenuAction::Left | MenuAction::Right => {
+					if let Ok(pack) = &self.packs[self.selected] {
+						let index = &mut self.puzzle_indices[self.selected];
+						*index = match action {
+							MenuAction::Right => (*index + 1).min(pack.puzzles.len() - 1),
+							_ => index.saturating_sub(1),
+						};
+					}
+				}
+				MenuAction::Confirm => {
+					if let Ok(pack) = &self.packs[self.selected] {
+						let puzzle_state = PuzzleState::new(pack.clone(), self.selected, self.puzzle_indices[self.selected]);
+						return Ok(NextUpdateAction::Switch(Box::new(puzzle_state)));
+					}
+				}
+				MenuAction::Back => {
+					return Ok(NextUpdateAction::Switch(Box::new(MenuState::new())));
+				}
+				MenuAction::Exit => return Ok(NextUpdateAction::Exit),
+				_ => {}
+			}
+		}
+
+		Ok(NextUpdateAction::Continue)
+	}
+
+	fn render_frame(&self, frame: &mut Frame) {
+		let mut lines = vec![
+			String::from("ГОЛОВОЛОМКИ"),
+			String::new(),
+		];
+
+		for (index, pack) in self.packs.iter().enumerate() {
+			let is_selected = index == self.selected;
+			let marker = if is_selected { '>' } else { ' ' };
+			match pack {
+				Ok(pack) if is_selected => {
+					let puzzle_index = self.puzzle_indices[index];
+					lines.push(format!(
+						"{marker} {} < {}/{}: {} >",
+						pack.name, puzzle_index + 1, pack.puzzles.len(), pack.puzzles[puzzle_index].name,
+					));
+				}
+				Ok(pack) => lines.push(format!("{marker} {} ({})", pack.name, pack.puzzles.len())),
+				Err(error) => lines.push(format!("{marker} ОШИБКА: {error}")),
+			}
+		}
+
+		lines.push(String::new());
+		lines.push(format!("Свои наборы: {}", storage::data_dir().join(PUZZLES_DIR).display()));
+		lines.push(String::from("↑/↓ — набор, ←/→ — головоломка"));
+		lines.push(String::from("Enter — начать, Esc — в меню"));
+
+		push_lines(frame, &lines);
+	}
+}
+
+/// Прохождение головоломки. Игрой управляет сам, без перехода на экран конца игры.
+pub struct PuzzleState {
+	pack: PuzzlePack,
+	/// Номер набора в списке, чтобы вернуться к нему
+	pack_index: usize,
+	puzzle_index: usize,
+	game: GameState,
+}
+
+impl PuzzleState {
+	fn new(pack: PuzzlePack, pack_index: usize, puzzle_index: usize) -> Self {
+		let game = GameState::from_puzzle(&pack.puzzles[puzzle_index]);
+		Self { pack, pack_index, puzzle_index, game }
+	}
+
+	fn puzzle(&self) -> &Puzzle {
+		&self.pack.puzzles[self.puzzle_index]
+	}
+
+	fn is_solved(&self) -> bool {
+		self.game.is_game_over() && self.game.result().completed
+	}
+
+	fn has_next(&self) -> bool {
+		self.puzzle_index + 1 < self.pack.puzzles.len()
+	}
+
+	fn restart(&mut self) {
+		self.game = GameState::from_puzzle(self.puzzle());
+	}
+
+	fn back_to_list(&self) -> NextUpdateAction {
+		NextUpdateAction::Switch(Box::new(PuzzlePackListState::with_selection(self.pack_index, self.puzzle_index)))
+	}
+}
+
+impl State for PuzzleState {
+	fn update(&mut self, context: &UpdateContext) -> std::io::Result<NextUpdateAction> {
+		for key_event in collect_last_key_events()? {
+			// После конца только Enter/Esc, чтобы не перезапустить головоломку лишним нажатием пробела
+			if self.game.is_game_over() {
+				if key_event.is_release() {
+					continue;
+				}
+				if is_interrupt(&key_event) {
+					return Ok(NextUpdateAction::Exit);
+				}
+				match key_event.code {
+					KeyCode::Enter if self.is_solved() && self.has_next() => {
+						self.puzzle_index += 1;
+						self.restart();
+					}
+					KeyCode::Enter => self.restart(),
+					KeyCode::Esc => return Ok(self.back_to_list()),
+					_ => {}
+				}
+				continue;
+			}
+
+			use PlayerAction::*;
+			match PlayerAction::from_key_event(key_event) {
+				Exit if is_interrupt(&key_event) => return Ok(NextUpdateAction::Exit),
+				Exit => return Ok(self.back_to_list()),
+				Restart => self.restart(),
+				TogglePause => self.game.toggle_pause(),
+				DoNothing => {}
+				action => self.game.apply_event(GameEvent::Action(action)),
+			}
+		}
+
+		self.game.advance_time(context.delta_time);
+		if self.game.is_gravity_due() {
+			self.game.apply_event(GameEvent::Gravity);
+		}
+
+		Ok(NextUpdateAction::Continue)
+	}
+
+	fn render_frame(&self, frame: &mut Frame) {
+		const GAP_BETWEEN_PARTS: usize = 4;
+
+		let mut game_part = Frame::new();
+		self.game.render_frame(&mut game_part);
+
+		let mut info_part = vec![
+			format!("{} — {}/{}", self.pack.name, self.puzzle_index + 1, self.pack.puzzles.len()),
+			self.puzzle().name.clone(),
+			String::new(),
+			format!("ЦЕЛЬ: {}", self.puzzle().goal),
+			format!("ВРЕМЯ: {}", format_time_precise(self.game.result().time)),
+			String::new(),
+		];
+
+		match (self.game.is_game_over(), self.is_solved()) {
+			(true, true) => {
+				info_part.push(String::from("РЕШЕНО!"));
+				info_part.push(String::from(match self.has_next() {
+					true => "Enter — следующая",
+					false => "Enter — ещё раз",
+				}));
+				info_part.push(String::from("Esc — к списку"));
+			}
+			(true, false) => {
+				info_part.push(String::from("НЕ РЕШЕНО"));
+				info_part.push(String::from("Enter — ещё раз"));
+				info_part.push(String::from("Esc — к списку"));
+			}
+			(false, _) => {
+				info_part.push(String::new());
+				info_part.push(String::from("R — заново"));
+				info_part.push(String::from("Esc — к списку"));
+			}
+		}
+
+		push_parts_side_by_side(frame, &game_part, &Frame::from_lines(&info_part), GAP_BETWEEN_PARTS);
+	}
+
+	fn spectator_frame(&self) -> Option<SpectatorFrame> {
+		self.game.spectator_frame()
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::input::PlayerAction;
+
+	fn builtin_puzzle(name: &str) -> Puzzle {
+		PuzzlePack::parse(BUILTIN_PACK).unwrap()
+			.puzzles.into_iter()
+			.find(|puzzle| puzzle.name == name)
+			.unwrap()
+	}
+
+	fn play(game: &mut GameState, actions: impl IntoIterator<Item = PlayerAction>) {
+		for action in actions {
+			game.apply_event(GameEvent::Action(action));
+		}
+	}
+
+	#[test]
+	fn rejects_invalid_packs() {
+		assert!(PuzzlePack::parse(BUILTIN_PACK).is_ok());
+		assert!(PuzzlePack::parse("{\"name\": \"оборван\"").is_err());
+		assert_eq!(
+			PuzzlePack::parse("{\"name\": \"пустой\", \"puzzles\": []}").err().unwrap(),
+			"в наборе нет головоломок",
+		);
+
+		let validate = |name: &str, board: &[&str], pieces: Vec<FigureKind>| PuzzlePack {
+			name: String::from("набор"),
+			puzzles: vec![Puzzle {
+				name: name.to_string(),
+				board: board.iter().map(|row| row.to_string()).collect(),
+				pieces,
+				goal: PuzzleGoal::PerfectClear,
+			}],
+		}.validate().err().unwrap();
+
+		let error = validate("кривая", &["##########", "####X#####"], vec![FigureKind::I]);
+		assert!(error.starts_with("головоломка 1 «кривая»: строка 2, столбец 5"), "{error}");
+		assert_eq!(
+			validate("узкая", &["#####"], vec![FigureKind::I]),
+			"головоломка 1 «узкая»: строка 1, столбец 6: строка короче первой (10)",
+		);
+		assert_eq!(
+			validate("без фигур", &[], Vec::new()),
+			"головоломка 1 «без фигур»: не задано ни одной фигуры",
+		);
+	}
+
+	#[test]
+	fn t_spin_double_solves_puzzle() {
+		let mut game = GameState::from_puzzle(&builtin_puzzle("Т-спин"));
+		// Т остриём вниз над щелью, затем поворот против часовой внутрь
+		play(&mut game, [PlayerAction::MoveLeft; 4]);
+		while game.current_position().y < BOARD_SIZE.height - 3 {
+			play(&mut game, [PlayerAction::MoveDown]);
+		}
+		play(&mut game, [PlayerAction::RotateCounterClockwise, PlayerAction::Drop]);
+
+		let result = game.result();
+		assert!(game.is_game_over());
+		assert!(result.completed);
+		assert_eq!(result.lines, 2);
+	}
+
+	#[test]
+	fn fails_when_pieces_run_out() {
+		let mut game = GameState::from_puzzle(&builtin_puzzle("Первая линия"));
+		// Палка падает стоя посреди поля и линию не закрывает
+		play(&mut game, [PlayerAction::Drop]);
+
+		assert!(game.is_game_over());
+		assert!(!game.result().completed);
+		assert_eq!(game.result().lines, 0);
+	}
+}
@@ -0,0 +1,337 @@
+use std::io::{self, Write};
+
+use crossterm::{
+	queue,
+	style::{Attribute, Attributes, Color, Colors, Print, SetAttribute, SetAttributes, SetColors},
+	terminal::{Clear, ClearType},
+	cursor::MoveTo,
+};
+
+type ColorTheme = (Color, Color);
+
+// Сделать бы стейт настроек с кастомизацией, а так только во время компиляции
+const _GREEN_THEME: ColorTheme = (Color::Rgb { r: 24, g: 190, b: 12 }, Color::Rgb { r: 4, g: 12, b: 2 });
+const _ORANGE_THEME: ColorTheme = (Color::Rgb { r: 255, g: 94, b: 0 }, Color::Rgb { r: 20, g: 8, b: 0 });
+const THEME: ColorTheme = _ORANGE_THEME;
+
+pub const FOREGROUND_COLOR: Color = THEME.0;
+pub const BACKGROUND_COLOR: Color = THEME.1;
+
+/// Оформление клетки кадра
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Style {
+	pub foreground: Color,
+	pub background: Color,
+	pub attributes: Attributes,
+}
+
+impl Default for Style {
+	/// Цвета темы, жирный шрифт
+	fn default() -> Self {
+		Self {
+			foreground: FOREGROUND_COLOR,
+			background: BACKGROUND_COLOR,
+			attributes: Attributes::from(Attribute::Bold),
+		}
+	}
+}
+
+impl Style {
+	pub fn with_attribute(mut self, attribute: Attribute) -> Self {
+		self.attributes.set(attribute);
+		self
+	}
+}
+
+/// Одна клетка терминала: символ и его оформление
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Cell {
+	pub glyph: char,
+	pub style: Style,
+}
+
+impl Cell {
+	pub fn new(glyph: char, style: Style) -> Self {
+		Self { glyph, style }
+	}
+}
+
+/// Строка кадра
+pub type Row = Vec<Cell>;
+
+/// Строка кадра из текста в одном оформлении
+pub fn styled(text: &str, style: Style) -> Row {
+	text.chars().map(|glyph| Cell::new(glyph, style)).collect()
+}
+
+/// Кадр: строки клеток сверху вниз. Строки могут быть разной длины.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Frame {
+	rows: Vec<Row>,
+}
+
+impl Frame {
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	/// Кадр из строк текста в оформлении по умолчанию
+	pub fn from_lines(lines: &[String]) -> Self {
+		Self { rows: lines.iter().map(|line| styled(line, Style::default())).collect() }
+	}
+
+	pub fn from_rows(rows: Vec<Row>) -> Self {
+		Self { rows }
+	}
+
+	pub fn rows(&self) -> &[Row] {
+		&self.rows
+	}
+
+	/// Ширина самой длинной строки
+	pub fn width(&self) -> usize {
+		self.rows.iter().map(Vec::len).max().unwrap_or(0)
+	}
+
+	pub fn height(&self) -> usize {
+		self.rows.len()
+	}
+
+	pub fn cell(&self, x: usize, y: usize) -> Option<&Cell> {
+		self.rows.get(y).and_then(|row| row.get(x))
+	}
+
+	pub fn push_row(&mut self, row: Row) {
+		self.rows.push(row);
+	}
+
+	/// Добавляет строку текста в оформлении по умолчанию
+	pub fn push_line(&mut self, line: &str) {
+		self.push_row(styled(line, Style::default()));
+	}
+
+	/// Добавляет строки другого кадра снизу
+	pub fn append(&mut self, other: &Frame) {
+		self.rows.extend(other.rows.iter().cloned());
+	}
+
+	pub fn clear(&mut self) {
+		self.rows.clear();
+	}
+
+	/// Кладёт кадр поверх этого, как терминал выводит новый кадр поверх старого:
+	/// клетки, которых в новом кадре нет, остаются прежними
+	pub fn overlay(&mut self, top: &Frame) {
+		for (y, row) in top.rows.iter().enumerate() {
+			if self.rows.len() <= y {
+				self.rows.resize(y + 1, Row::new());
+			}
+			let own_row = &mut self.rows[y];
+			if own_row.len() < row.len() {
+				own_row.resize(row.len(), Cell::new(' ', Style::default()));
+			}
+			own_row[..row.len()].copy_from_slice(row);
+		}
+	}
+
+	/// Текст кадра без оформления, по строке на строку кадра
+	pub fn lines(&self) -> Vec<String> {
+		self.rows.iter()
+			.map(|row| row.iter().map(|cell| cell.glyph).collect())
+			.collect()
+	}
+}
+
+/// Вывод готового кадра
+pub trait Renderer {
+	fn draw(&mut self, frame: &Frame) -> io::Result<()>;
+	/// Стирает всё выведенное (при смене экрана, чтобы не осталось следов старого)
+	fn clear(&mut self) -> io::Result<()>;
+}
+
+/// Вывод в терминал через crossterm. Помнит, что уже на экране, и выводит
+/// только изменившиеся клетки: команды копятся в буфере и уходят одним сбросом,
+/// так что по SSH и в медленных терминалах кадр не мерцает.
+pub struct TerminalRenderer<W: Write> {
+	out: W,
+	/// Что сейчас на экране
+	screen: Frame,
+	/// Оформление, в котором терминал сейчас печатает; `None`, если неизвестно
+	style: Option<Style>,
+}
+
+impl<W: Write> TerminalRenderer<W> {
+	pub fn new(out: W) -> Self {
+		Self { out, screen: Frame::new(), style: None }
+	}
+
+	fn set_style(&mut self, style: Style) -> io::Result<()> {
+		if self.style == Some(style) {
+			return Ok(());
+		}
+		// Отдельно снять атрибут нельзя (NoBold почему-то включает подчёркивание),
+		// поэтому оформление сбрасывается целиком и задаётся заново
+		queue!(
+			self.out,
+			SetAttribute(Attribute::Reset),
+			SetColors(Colors::new(style.foreground, style.background)),
+			SetAttributes(style.attributes),
+		)?;
+		self.style = Some(style);
+		Ok(())
+	}
+}
+
+impl<W: Write> Renderer for TerminalRenderer<W> {
+	fn draw(&mut self, frame: &Frame) -> io::Result<()> {
+		// Подряд идущие изменённые клетки одного оформления печатаются одним куском
+		let mut run = String::new();
+		let mut run_style = None;
+		// Куда встанет курсор после печати куска
+		let mut cursor = None;
+
+		for (y, row) in frame.rows().iter().enumerate() {
+			for (x, cell) in row.iter().enumerate() {
+				if self.screen.cell(x, y) == Some(cell) {
+					continue;
+				}
+
+				if cursor != Some((x, y)) || run_style != Some(cell.style) {
+					if let Some(style) = run_style {
+						self.set_style(style)?;
+						queue!(self.out, Print(&run))?;
+						run.clear();
+					}
+					if cursor != Some((x, y)) {
+						queue!(self.out, MoveTo(x as u16, y as u16))?;
+					}
+					run_style = Some(cell.style);
+				}
+				run.push(cell.glyph);
+				cursor = Some((x + 1, y));
+			}
+		}
+		if let Some(style) = run_style {
+			self.set_style(style)?;
+			queue!(self.out, Print(&run))?;
+		}
+
+		self.screen.overlay(frame);
+		self.out.flush()
+	}
+
+	fn clear(&mut self) -> io::Result<()> {
+		queue!(self.out, Clear(ClearType::All))?;
+		self.out.flush()?;
+		// Экран пуст: следующий кадр выводится целиком
+		self.screen.clear();
+		self.style = None;
+		Ok(())
+	}
+}
+
+/// Вывод в память: ведёт себя как экран терминала (новый кадр ложится поверх
+/// старого), но ничего не печатает. Для проверок без терминала.
+#[derive(Default)]
+pub struct MemoryRenderer {
+	screen: Frame,
+	draws: usize,
+}
+
+impl MemoryRenderer {
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	/// Что сейчас было бы видно на экране
+	pub fn screen(&self) -> &Frame {
+		&self.screen
+	}
+
+	/// Сколько кадров выведено
+	pub fn draws(&self) -> usize {
+		self.draws
+	}
+}
+
+impl Renderer for MemoryRenderer {
+	fn draw(&mut self, frame: &Frame) -> io::Result<()> {
+		self.screen.overlay(frame);
+		self.draws += 1;
+		Ok(())
+	}
+
+	fn clear(&mut self) -> io::Result<()> {
+		self.screen.clear();
+		Ok(())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn lines(texts: &[&str]) -> Vec<String> {
+		texts.iter().map(|text| text.to_string()).collect()
+	}
+
+	#[test]
+	fn memory_renderer_keeps_cells_outside_new_frame() {
+		let mut renderer = MemoryRenderer::new();
+		renderer.draw(&Frame::from_lines(&lines(&["ТЕТРИС", "ПАУЗА", "Esc"]))).unwrap();
+		renderer.draw(&Frame::from_lines(&lines(&["ИГРА"]))).unwrap();
+
+		// Как на терминале: короткий кадр не стирает хвост строки и строки ниже
+		assert_eq!(renderer.draws(), 2);
+		assert_eq!(renderer.screen().height(), 3);
+		assert_eq!(renderer.screen().lines(), lines(&["ИГРАИС", "ПАУЗА", "Esc"]));
+
+		renderer.clear().unwrap();
+		renderer.draw(&Frame::from_lines(&lines(&["МЕНЮ"]))).unwrap();
+		assert_eq!(renderer.screen().lines(), lines(&["МЕНЮ"]));
+	}
+
+	/// Что рендерер вывел с прошлого вызова
+	fn take_output(renderer: &mut TerminalRenderer<Vec<u8>>) -> String {
+		String::from_utf8(std::mem::take(&mut renderer.out)).unwrap()
+	}
+
+	#[test]
+	fn terminal_renderer_skips_unchanged_frame() {
+		let mut renderer = TerminalRenderer::new(Vec::new());
+		let frame = Frame::from_lines(&lines(&["ТЕТРИС", "<!..!>"]));
+		renderer.draw(&frame).unwrap();
+		assert!(take_output(&mut renderer).contains("ТЕТРИС"));
+
+		renderer.draw(&frame).unwrap();
+		assert_eq!(take_output(&mut renderer), "");
+	}
+
+	#[test]
+	fn terminal_renderer_writes_only_changed_cell() {
+		let mut renderer = TerminalRenderer::new(Vec::new());
+		renderer.draw(&Frame::from_lines(&lines(&["ТЕТРИС", "<!..!>"]))).unwrap();
+		take_output(&mut renderer);
+
+		renderer.draw(&Frame::from_lines(&lines(&["ТЕТРИС", "<!.#!>"]))).unwrap();
+		// Оформление не менялось: только перевод курсора (строка 2, столбец 4) и сама клетка
+		assert_eq!(take_output(&mut renderer), "\x1b[2;4H#");
+	}
+
+	#[test]
+	fn terminal_renderer_resets_attributes_between_styles() {
+		let dim = Style::default().with_attribute(Attribute::Dim);
+		let mut row = styled("ab", Style::default());
+		row.extend(styled("cd", dim));
+		let mut renderer = TerminalRenderer::new(Vec::new());
+		renderer.draw(&Frame::from_rows(vec![row])).unwrap();
+
+		let output = take_output(&mut renderer);
+		let reset = "\x1b[0m";
+		assert_eq!(output.matches(reset).count(), 2);
+		let bold_run = output.find("ab").unwrap();
+		let dim_run = output.find("cd").unwrap();
+		let second_reset = output.rfind(reset).unwrap();
+		assert!(bold_run < second_reset && second_reset < dim_run, "{output:?}");
+	}
+}
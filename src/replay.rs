@@ -0,0 +1,329 @@
+use std::time::Duration;
+
+use serde::{Serialize, Deserialize};
+
+use crate::shared::*;
+use crate::input::*;
+use crate::ui::*;
+use crate::state::*;
+use crate::render::Frame;
+use crate::spectator::SpectatorFrame;
+use crate::storage;
+use crate::board::Board;
+use crate::game::{GameEvent, GameResult, GameState};
+use crate::mode::{GameMode, StackVisibility};
+use crate::menu::MenuState;
+
+const REPLAYS_DIR: &str = "replays";
+/// Версия формата файла повтора. Повторы другой версии не воспроизводятся.
+const REPLAY_VERSION: u32 = 1;
+
+const PLAYBACK_SPEEDS: [u32; 4] = [1, 2, 4, 8];
+const VISIBLE_LIST_ROWS: usize = 15;
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct ReplayEvent {
+	/// Игровое время события (без пауз), в миллисекундах
+	pub time_ms: u64,
+	pub event: GameEvent,
+}
+impl ReplayEvent {
+	pub fn new(time: Duration, event: GameEvent) -> Self {
+		Self { time_ms: time.as_millis() as u64, event }
+	}
+
+	pub fn time(&self) -> Duration {
+		Duration::from_millis(self.time_ms)
+	}
+}
+
+/// Запись игры: seed и события с метками времени. Итоги хранятся для списка
+/// повторов и для сверки после воспроизведения.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Replay {
+	pub version: u32,
+	pub mode: GameMode,
+	pub start_level: u8,
+	pub seed: u64,
+	#[serde(default)]
+	pub stack_visibility: StackVisibility,
+	/// Стартовая доска в виде `Board::to_ascii`, если игра началась не с пустой (например, из fumen)
+	#[serde(default)]
+	pub initial_board: Option<String>,
+	/// Unix-время в миллисекундах
+	pub date_ms: u64,
+
+	pub score: u32,
+	pub lines: u16,
+	pub time_ms: u64,
+
+	pub events: Vec<ReplayEvent>,
+}
+
+impl Replay {
+	pub fn new(result: GameResult, start_level: u8, stack_visibility: StackVisibility, events: Vec<ReplayEvent>) -> Self {
+		Self {
+			version: REPLAY_VERSION,
+			mode: result.mode,
+			start_level,
+			seed: result.seed,
+			stack_visibility,
+			initial_board: None,
+			date_ms: unix_time_now().as_millis() as u64,
+
+			score: result.score,
+			lines: result.lines,
+			time_ms: result.time.as_millis() as u64,
+
+			events,
+		}
+	}
+
+	pub fn with_initial_board(mut self, initial_board: Option<String>) -> Self {
+		self.initial_board = initial_board;
+		self
+	}
+
+	pub fn save(&self) -> std::io::Result<()> {
+		storage::save_json(&format!("{REPLAYS_DIR}/{}.json", self.date_ms), self)
+	}
+
+	/// Загружает все сохранённые повторы, от новых к старым. Повреждённые файлы пропускаются.
+	pub fn load_all() -> std::io::Result<Vec<Replay>> {
+		let mut replays: Vec<Replay> = storage::list_json_files(REPLAYS_DIR)?
+			.iter()
+			.filter_map(|file_name| storage::read_json(file_name).ok())
+			.filter(|replay: &Replay| replay.version == REPLAY_VERSION)
+			.collect();
+		replays.sort_by_key(|replay| std::cmp::Reverse(replay.date_ms));
+
+		Ok(replays)
+	}
+
+	pub fn time(&self) -> Duration {
+		Duration::from_millis(self.time_ms)
+	}
+}
+
+/// Экран со списком сохранённых повторов
+pub struct ReplayListState {
+	replays: Vec<Replay>,
+	selected: usize,
+	error_message: Option<String>,
+}
+
+impl ReplayListState {
+	pub fn new() -> Self {
+		let (replays, error_message) = match Replay::load_all() {
+			Ok(replays) => (replays, None),
+			Err(error) => (Vec::new(), Some(format!("Не удалось загрузить повторы: {error}"))),
+		};
+
+		Self { replays, selected: 0, error_message }
+	}
+}
+
+impl Default for ReplayListState {
+	fn default() -> Self {
+		Self::new()
+	}
+}
+
+impl State for ReplayListState {
+	fn update(&mut self, _context: &UpdateContext) -> std::io::Result<NextUpdateAction> {
+		for key_event in collect_last_key_events()? {
+			match MenuAction::from_key_event(key_event) {
+				MenuAction::Up if !self.replays.is_empty() => {
+					self.selected = (self.selected + self.replays.len() - 1) % self.replays.len();
+				}
+				MenuAction::Down if !self.replays.is_empty() => {
+					self.selected = (self.selected + 1) % self.replays.len();
+				}
+				MenuAction::Confirm if !self.replays.is_empty() => {
+					let replay = self.replays.swap_remove(self.selected);
+					return Ok(NextUpdateAction::Switch(Box::new(ReplayState::new(replay))));
+				}
+				MenuAction::Back => {
+					return Ok(NextUpdateAction::Switch(Box::new(MenuState::new())));
+				}
+				MenuAction::Exit => return Ok(NextUpdateAction::Exit),
+				_ => {}
+			}
+		}
+
+		Ok(NextUpdateAction::Continue)
+	}
+
+	fn render_frame(&self, frame: &mut Frame) {
+		let mut lines = vec![
+			String::from("ПОВТОРЫ"),
+			String::new(),
+			format!("  {:<10}  {:<16}  {:>8}  {:>5}  {:>6}", "ДАТА", "РЕЖИМ", "СЧЁТ", "ЛИНИИ", "ВРЕМЯ"),
+		];
+
+		let first_visible = self.selected.saturating_sub(VISIBLE_LIST_ROWS - 1);
+		for (index, replay) in self.replays.iter().enumerate().skip(first_visible).take(VISIBLE_LIST_ROWS) {
+			let marker = if index == self.selected { '>' } else { ' ' };
+			lines.push(format!(
+				"{marker} {:<10}  {:<16}  {:>8}  {:>5}  {:>6}",
+				format_date(replay.date_ms / 1000), replay.mode.to_string(),
+				replay.score, replay.lines, format_time(replay.time()),
+			));
+		}
+		if self.replays.is_empty() {
+			lines.push(String::from("  (пусто)"));
+		}
+		// Строки-заглушки, чтобы перекрыть остатки длинного списка
+		while lines.len() < VISIBLE_LIST_ROWS + 3 {
+			lines.push(String::new());
+		}
+
+		lines.push(String::new());
+		if let Some(message) = &self.error_message {
+			lines.push(message.clone());
+		}
+		lines.push(String::from("↑/↓ — выбор, Enter — смотреть, Esc — в меню"));
+
+		push_lines(frame, &lines);
+	}
+}
+
+/// Воспроизведение повтора: события подаются в `GameState` в те же моменты игрового времени
+pub struct ReplayState {
+	replay: Replay,
+	game: GameState,
+	next_event_index: usize,
+	speed_index: usize,
+	is_paused: bool,
+}
+
+impl ReplayState {
+	pub fn new(replay: Replay) -> Self {
+		let mut game = GameState::new(replay.mode, replay.start_level, replay.seed)
+			.with_stack_visibility(replay.stack_visibility);
+		if let Some(board) = replay.initial_board.as_deref().and_then(|text| Board::from_ascii(text).ok()) {
+			game = game.with_board(board);
+		}
+		Self {
+			replay,
+			game,
+			next_event_index: 0,
+			speed_index: 0,
+			is_paused: false,
+		}
+	}
+
+	/// Все события применены и игровое время дошло до конца записи
+	fn is_finished(&self) -> bool {
+		self.next_event_index >= self.replay.events.len()
+			&& (self.game.is_game_over() || self.game.result().time >= self.replay.time())
+	}
+
+	/// Продвигает воспроизведение на `delta` и применяет наступившие события
+	fn advance(&mut self, delta: Duration) {
+		self.game.advance_time(delta * PLAYBACK_SPEEDS[self.speed_index]);
+
+		let now = self.game.result().time;
+		while let Some(replay_event) = self.replay.events.get(self.next_event_index) {
+			// После конца игры секундомер стоит, оставшиеся события применяются сразу
+			if replay_event.time() > now && !self.game.is_game_over() {
+				break;
+			}
+			self.game.apply_event(replay_event.event);
+			self.next_event_index += 1;
+		}
+	}
+}
+
+impl State for ReplayState {
+	fn update(&mut self, context: &UpdateContext) -> std::io::Result<NextUpdateAction> {
+		for key_event in collect_last_key_events()? {
+			match MenuAction::from_key_event(key_event) {
+				MenuAction::Confirm => self.is_paused = !self.is_paused,
+				MenuAction::Right | MenuAction::Up => {
+					self.speed_index = (self.speed_index + 1).min(PLAYBACK_SPEEDS.len() - 1);
+				}
+				MenuAction::Left | MenuAction::Down => {
+					self.speed_index = self.speed_index.saturating_sub(1);
+				}
+				MenuAction::Back => {
+					return Ok(NextUpdateAction::Switch(Box::new(ReplayListState::new())));
+				}
+				MenuAction::Exit => return Ok(NextUpdateAction::Exit),
+				_ => {}
+			}
+		}
+
+		if !self.is_paused && !self.is_finished() {
+			self.advance(context.delta_time);
+		}
+
+		Ok(NextUpdateAction::Continue)
+	}
+
+	fn render_frame(&self, frame: &mut Frame) {
+		const GAP_BETWEEN_PARTS: usize = 4;
+
+		let mut game_part = Frame::new();
+		self.game.render_frame(&mut game_part);
+
+		let status = if self.is_finished() {
+			"ОКОНЧЕН"
+		} else if self.is_paused {
+			"ПАУЗА"
+		} else {
+			"ИДЁТ"
+		};
+
+		let mut info_part = vec![
+			String::from("ПОВТОР"),
+			String::new(),
+			format!("РЕЖИМ:    {}", self.replay.mode),
+			format!("ДАТА:     {}", format_date(self.replay.date_ms / 1000)),
+			format!("SEED:     {:016x}", self.replay.seed),
+			format!("СКОРОСТЬ: x{}", PLAYBACK_SPEEDS[self.speed_index]),
+			format!("СТАТУС:   {status}"),
+		];
+
+		if self.is_finished() && self.game.result().score != self.replay.score {
+			info_part.push(String::new());
+			info_part.push(format!("РАСХОЖДЕНИЕ! ОЖИДАЛСЯ СЧЁТ {}", self.replay.score));
+		}
+
+		info_part.push(String::new());
+		info_part.push(String::from("Enter/Пробел — пауза"));
+		info_part.push(String::from("←/→ — скорость"));
+		info_part.push(String::from("Esc — к списку"));
+
+		push_parts_side_by_side(frame, &game_part, &Frame::from_lines(&info_part), GAP_BETWEEN_PARTS);
+	}
+
+	fn spectator_frame(&self) -> Option<SpectatorFrame> {
+		self.game.spectator_frame()
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::input::PlayerAction;
+
+	#[test]
+	fn replay_starts_from_custom_board() {
+		let mut text = String::from("..........\n").repeat(16);
+		text.push_str("#########.\n".repeat(4).as_str());
+		let board = Board::from_ascii(&text).unwrap();
+
+		let mut game = GameState::new(GameMode::Zen { gravity: false }, 0, 7).with_board(board);
+		for _ in 0..3 {
+			game.apply_event(GameEvent::Action(PlayerAction::Drop));
+			game.advance_time(Duration::from_millis(100));
+		}
+		assert_ne!(game.board().to_ascii(), text);
+
+		let mut playback = ReplayState::new(game.replay());
+		playback.advance(Duration::from_secs(1));
+		assert_eq!(playback.game.board().to_ascii(), game.board().to_ascii());
+		assert_eq!(playback.game.result().score, game.result().score);
+	}
+}
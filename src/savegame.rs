@@ -0,0 +1,94 @@
+use std::io;
+
+use serde::{Serialize, Deserialize};
+
+use crate::storage;
+use crate::game::GameState;
+
+const SAVE_FILE: &str = "savegame.json";
+/// Версия формата сохранения. Сохранение другой версии не загружается.
+const SAVE_VERSION: u32 = 3;
+
+#[derive(Serialize)]
+struct SavedGameRef<'a> {
+	version: u32,
+	game: &'a GameState,
+}
+
+/// Игра разбирается только после проверки версии: сохранение другого формата
+/// могло бы разобраться в бессмысленное состояние
+#[derive(Deserialize)]
+struct SavedGame {
+	version: u32,
+	game: serde_json::Value,
+}
+
+/// Сохраняет незаконченную игру, чтобы продолжить её при следующем запуске
+pub fn save(game: &GameState) -> io::Result<()> {
+	storage::save_json(SAVE_FILE, &SavedGameRef { version: SAVE_VERSION, game })
+}
+
+pub fn exists() -> bool {
+	storage::exists(SAVE_FILE)
+}
+
+/// Загружает сохранённую игру и удаляет сохранение, чтобы одну игру нельзя было
+/// продолжить дважды. Игра возвращается на паузе.
+pub fn take() -> io::Result<GameState> {
+	// Удаляется и непрочитанное сохранение, иначе ошибка будет повторяться при каждом запуске
+	let saved: io::Result<SavedGame> = storage::read_json(SAVE_FILE);
+	storage::remove(SAVE_FILE)?;
+	let saved = saved?;
+
+	if saved.version != SAVE_VERSION {
+		return Err(io::Error::new(
+			io::ErrorKind::InvalidData,
+			format!("версия сохранения {} не поддерживается", saved.version),
+		));
+	}
+
+	let mut game: GameState = serde_json::from_value(saved.game)
+		.map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error))?;
+	game.pause();
+	Ok(game)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::game::GameEvent;
+	use crate::input::PlayerAction;
+	use crate::mode::GameMode;
+
+	#[test]
+	fn save_and_take_round_trip() {
+		storage::use_temp_data_dir();
+
+		let mut game = GameState::new(GameMode::Marathon, 0, 5);
+		for action in [PlayerAction::MoveLeft, PlayerAction::Drop, PlayerAction::RotateClockwise, PlayerAction::Drop] {
+			game.apply_event(GameEvent::Action(action));
+		}
+		save(&game).unwrap();
+		assert!(exists());
+
+		let restored = take().unwrap();
+		assert!(!exists());
+		assert!(restored.is_paused());
+		assert_eq!(restored.board().to_ascii(), game.board().to_ascii());
+		assert_eq!(restored.result().score, game.result().score);
+		assert_eq!(restored.pieces_placed(), 2);
+		let saved = serde_json::to_value(&game).unwrap();
+		let loaded = serde_json::to_value(&restored).unwrap();
+		assert!(!saved["rng"].is_null());
+		assert_eq!(loaded["rng"], saved["rng"]);
+		assert_eq!(loaded["next_figure"], saved["next_figure"]);
+
+		// Сохранение прошлой версии не разбирается и удаляется
+		let old = serde_json::json!({ "version": SAVE_VERSION - 1, "game": { "board": "?" } });
+		storage::save_json(SAVE_FILE, &old).unwrap();
+		let error = take().err().unwrap();
+		assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+		assert!(error.to_string().contains("версия сохранения"), "{error}");
+		assert!(!exists());
+	}
+}
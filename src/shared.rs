@@ -1,6 +1,8 @@
-use std::time::{Duration, Instant};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
-#[derive(Debug, Clone, Copy)]
+use serde::{Serialize, Deserialize};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct Point {
 	pub x: usize,
 	pub y: usize,
@@ -12,7 +14,7 @@ impl Point {
 	}
 }
 
-#[derive(Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub struct Size {
 	pub height: usize,
 	pub width: usize,
@@ -27,15 +29,18 @@ impl Size {
 	}
 }
 
+/// Секундомер, время которого продвигается вручную через `advance`, а не берётся
+/// из системных часов: так игру можно детерминированно воспроизвести.
+#[derive(Default, Serialize, Deserialize)]
 pub struct Stopwatch {
 	total: Duration,
-	start_time: Option<Instant>,
+	is_running: bool,
 }
 impl Stopwatch {
 	pub fn new() -> Self {
 		Self {
 			total: Duration::ZERO,
-			start_time: None,
+			is_running: false,
 		}
 	}
 
@@ -47,23 +52,60 @@ impl Stopwatch {
 	}
 
 	pub fn start(&mut self) {
-		if self.start_time.is_none() {
-			self.start_time = Some(Instant::now())
-		}
+		self.is_running = true;
 	}
 
 	pub fn pause(&mut self) {
-		if let Some(start_time) = self.start_time {
-			self.total += start_time.elapsed();
-			self.start_time = None;
+		self.is_running = false;
+	}
+
+	pub fn is_running(&self) -> bool {
+		self.is_running
+	}
+
+	/// Продвигает время на `delta`, если секундомер запущен
+	pub fn advance(&mut self, delta: Duration) {
+		if self.is_running {
+			self.total += delta;
 		}
 	}
 
 	pub fn elapsed(&self) -> Duration {
-		if let Some(start_time) = self.start_time {
-			self.total + start_time.elapsed()
-		} else {
-			self.total
-		}
+		self.total
 	}
 }
+
+/// Текущее unix-время
+pub fn unix_time_now() -> Duration {
+	SystemTime::now()
+		.duration_since(UNIX_EPOCH)
+		.unwrap_or(Duration::ZERO)
+}
+
+/// Время в виде `м:сс`
+pub fn format_time(time: Duration) -> String {
+	let total_seconds = time.as_secs();
+	format!("{}:{:02}", total_seconds / 60, total_seconds % 60)
+}
+
+/// Время в виде `м:сс.ммм`
+pub fn format_time_precise(time: Duration) -> String {
+	let total_seconds = time.as_secs();
+	format!("{}:{:02}.{:03}", total_seconds / 60, total_seconds % 60, time.subsec_millis())
+}
+
+/// Дата в виде `ГГГГ-ММ-ДД` (UTC) из unix-времени в секундах
+pub fn format_date(unix_seconds: u64) -> String {
+	// Алгоритм civil_from_days (Howard Hinnant)
+	let days = (unix_seconds / 86_400) as i64 + 719_468;
+	let era = days.div_euclid(146_097);
+	let day_of_era = days.rem_euclid(146_097);
+	let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+	let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+	let mp = (5 * day_of_year + 2) / 153;
+	let day = day_of_year - (153 * mp + 2) / 5 + 1;
+	let month = if mp < 10 { mp + 3 } else { mp - 9 };
+	let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+
+	format!("{year:04}-{month:02}-{day:02}")
+}
@@ -0,0 +1,270 @@
+use std::io::{self, Write};
+use std::iter;
+use std::net::{TcpListener, TcpStream, ToSocketAddrs};
+
+use crossterm::event::KeyCode;
+use serde::{Serialize, Deserialize};
+
+use crate::input::*;
+use crate::ui::*;
+use crate::state::*;
+use crate::render::Frame;
+use crate::board::ASCII_FILLED_CELL;
+use crate::figure::{Figure, FigureKind};
+use crate::game::BOARD_SIZE;
+use crate::net::{CloseReason, Connection};
+
+/// Снимок идущей игры для зрителей: всё, что нужно, чтобы нарисовать доску и панель
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SpectatorFrame {
+	/// Название режима
+	pub mode: String,
+	/// Показатели режима: подпись и значение
+	pub statistics: Vec<(String, String)>,
+	/// Доска вместе с падающей фигурой (см. `GameState::field_rows`)
+	pub rows: Vec<String>,
+	pub next: Option<FigureKind>,
+	pub is_paused: bool,
+	pub is_game_over: bool,
+}
+
+/// Сообщения трансляции, по одному JSON в строке
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub enum SpectatorMessage {
+	Frame(SpectatorFrame),
+	/// Игрок сейчас не в игре (меню, таблица рекордов и т.д.)
+	Idle,
+}
+
+/// Раздаёт снимки игры всем подключившимся зрителям. Снимок отправляется только
+/// когда он изменился, а новый зритель сразу получает последний.
+pub struct Broadcaster {
+	listener: TcpListener,
+	spectators: Vec<TcpStream>,
+	last_message: Option<SpectatorMessage>,
+}
+
+impl Broadcaster {
+	pub fn bind(address: impl ToSocketAddrs) -> io::Result<Self> {
+		let listener = TcpListener::bind(address)?;
+		listener.set_nonblocking(true)?;
+		Ok(Self { listener, spectators: Vec::new(), last_message: None })
+	}
+
+	pub fn publish(&mut self, frame: Option<SpectatorFrame>) {
+		let message = match frame {
+			Some(frame) => SpectatorMessage::Frame(frame),
+			None => SpectatorMessage::Idle,
+		};
+
+		let mut newcomers = Vec::new();
+		while let Ok((stream, _)) = self.listener.accept() {
+			// Медленный зритель не должен тормозить игру: если он не успевает
+			// читать, запись не блокируется, а завершается ошибкой, и он отключается
+			if stream.set_nonblocking(true).is_ok() {
+				newcomers.push(stream);
+			}
+		}
+
+		let Ok(mut line) = serde_json::to_string(&message) else { return };
+		line.push('\n');
+
+		if self.last_message.as_ref() != Some(&message) {
+			self.spectators.retain_mut(|stream| stream.write_all(line.as_bytes()).is_ok());
+			self.last_message = Some(message);
+		}
+		newcomers.retain_mut(|stream| stream.write_all(line.as_bytes()).is_ok());
+		self.spectators.append(&mut newcomers);
+	}
+}
+
+/// Клиент трансляции: только показывает чужую игру
+pub struct SpectatorState {
+	connection: Connection<SpectatorMessage, ()>,
+	address: String,
+	message: Option<SpectatorMessage>,
+}
+
+impl SpectatorState {
+	pub fn connect(address: &str) -> io::Result<Self> {
+		let stream = TcpStream::connect(address)?;
+		Ok(Self {
+			connection: Connection::new(stream)?,
+			address: String::from(address),
+			message: None,
+		})
+	}
+}
+
+impl State for SpectatorState {
+	fn update(&mut self, _context: &UpdateContext) -> std::io::Result<NextUpdateAction> {
+		for key_event in collect_last_key_events()? {
+			if key_event.is_release() {
+				continue;
+			}
+			if is_interrupt(&key_event) || key_event.code == KeyCode::Esc {
+				return Ok(NextUpdateAction::Exit);
+			}
+		}
+
+		if let Some(message) = self.connection.receive().pop() {
+			self.message = Some(message);
+		}
+
+		Ok(NextUpdateAction::Continue)
+	}
+
+	fn render_frame(&self, frame: &mut Frame) {
+		const GAP_BETWEEN_PARTS: usize = 2;
+		const BLANK_WIDTH: usize = 48;
+
+		let mut header = vec![format!("ТРАНСЛЯЦИЯ {}", self.address)];
+		match self.connection.close_reason() {
+			Some(CloseReason::ProtocolMismatch(error)) => {
+				header.push(format!("Непонятная трансляция (другая версия игры?): {error}"));
+			}
+			Some(CloseReason::Disconnected) => header.push(String::from("Трансляция завершена")),
+			None => {}
+		}
+		header.push(String::from("Esc — выход"));
+		header.push(String::new());
+
+		let snapshot = match &self.message {
+			Some(SpectatorMessage::Frame(snapshot)) => snapshot,
+			status => {
+				header.push(String::from(match status {
+					Some(_) => "Игрок сейчас не в игре",
+					None => "Ожидание игры...",
+				}));
+				// Экран не очищается при смене снимка на ожидание: затираем остатки доски
+				header.extend(iter::repeat_n(" ".repeat(BLANK_WIDTH), BOARD_SIZE.height + 1));
+				push_lines(frame, &header);
+				return;
+			}
+		};
+		push_lines(frame, &header);
+
+		let mut statistics_part = vec![snapshot.mode.clone(), String::new()];
+		let labels_width = snapshot.statistics.iter().map(|(label, _)| label.chars().count()).max().unwrap_or(0);
+		statistics_part.extend(snapshot.statistics.iter()
+			.map(|(label, value)| format!("{label:<labels_width$} {value}"))
+		);
+		if let Some(kind) = snapshot.next {
+			let figure = Figure::of_kind(kind);
+			statistics_part.push(String::new());
+			for row in 0..figure.size.height {
+				let mut line = String::new();
+				for col in 0..figure.size.width {
+					line.push_pixel(match figure.cells[row * figure.size.width + col] {
+						true => ['[', ']'],
+						false => [' ', ' '],
+					});
+				}
+				statistics_part.push(line);
+			}
+		}
+		if snapshot.is_game_over {
+			statistics_part.push(String::new());
+			statistics_part.push(String::from("ИГРА ОКОНЧЕНА"));
+		} else if snapshot.is_paused {
+			statistics_part.push(String::new());
+			statistics_part.push(String::from("ПАУЗА"));
+		}
+
+		let mut board_part: Vec<String> = snapshot.rows.iter()
+			.map(|row| {
+				let mut line = String::from("<!");
+				for cell in row.chars() {
+					line.push_pixel(match cell == ASCII_FILLED_CELL {
+						true => ['[', ']'],
+						false => [' ', '.'],
+					});
+				}
+				line.push_str("!>");
+				line
+			})
+			.collect();
+		let board_width = snapshot.rows.first().map_or(0, |row| row.chars().count());
+		board_part.push(format!("<!{}!>", "==".repeat(board_width)));
+
+		push_parts_side_by_side(frame, &Frame::from_lines(&statistics_part), &Frame::from_lines(&board_part), GAP_BETWEEN_PARTS);
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::io::{BufRead, BufReader};
+	use std::time::Duration;
+
+	use super::*;
+	use crate::board::Board;
+	use crate::game::GameState;
+	use crate::mode::{GameMode, StackVisibility};
+
+	fn frame(mode: &str) -> SpectatorFrame {
+		SpectatorFrame {
+			mode: String::from(mode),
+			statistics: Vec::new(),
+			rows: Vec::new(),
+			next: None,
+			is_paused: false,
+			is_game_over: false,
+		}
+	}
+
+	struct Spectator(BufReader<TcpStream>);
+
+	impl Spectator {
+		fn connect(broadcaster: &Broadcaster) -> Self {
+			let stream = TcpStream::connect(broadcaster.listener.local_addr().unwrap()).unwrap();
+			stream.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+			Self(BufReader::new(stream))
+		}
+
+		fn next_message(&mut self) -> SpectatorMessage {
+			let mut line = String::new();
+			self.0.read_line(&mut line).unwrap();
+			serde_json::from_str(&line).unwrap()
+		}
+	}
+
+	#[test]
+	fn broadcaster_sends_only_changes() {
+		let mut broadcaster = Broadcaster::bind("127.0.0.1:0").unwrap();
+		broadcaster.publish(Some(frame("A")));
+
+		// Новый зритель сразу получает последний снимок, а повтор того же снимка не отправляется
+		let mut first = Spectator::connect(&broadcaster);
+		broadcaster.publish(Some(frame("A")));
+		broadcaster.publish(Some(frame("A")));
+		broadcaster.publish(Some(frame("B")));
+		assert_eq!(first.next_message(), SpectatorMessage::Frame(frame("A")));
+		assert_eq!(first.next_message(), SpectatorMessage::Frame(frame("B")));
+
+		let mut second = Spectator::connect(&broadcaster);
+		broadcaster.publish(Some(frame("B")));
+		broadcaster.publish(None);
+		assert_eq!(second.next_message(), SpectatorMessage::Frame(frame("B")));
+		assert_eq!(second.next_message(), SpectatorMessage::Idle);
+		assert_eq!(first.next_message(), SpectatorMessage::Idle);
+	}
+
+	#[test]
+	fn hidden_stack_is_hidden_from_spectators() {
+		let mut text = String::from("..........\n").repeat(19);
+		text.push_str("#########.\n");
+		let board = Board::from_ascii(&text).unwrap();
+		let rows = |visibility| GameState::new(GameMode::Marathon, 0, 1)
+			.with_board(board.clone())
+			.with_stack_visibility(visibility)
+			.spectator_frame()
+			.unwrap()
+			.rows;
+
+		assert_eq!(rows(StackVisibility::Visible).last().unwrap(), "#########.");
+		assert_eq!(rows(StackVisibility::Invisible).last().unwrap(), "..........");
+		// Падающая фигура видна и при невидимом стакане
+		assert!(rows(StackVisibility::Invisible)[0].contains(ASCII_FILLED_CELL));
+	}
+}
@@ -0,0 +1,26 @@
+use std::time::{Duration, Instant};
+
+use crate::spectator::SpectatorFrame;
+use crate::render::Frame;
+
+pub struct UpdateContext {
+	pub frame_start_time: Instant,
+	/// Время, прошедшее с начала предыдущего кадра
+	pub delta_time: Duration,
+}
+pub enum NextUpdateAction {
+	Continue,
+	/// Заменить текущее состояние другим (экран меню, конец игры и т.д.)
+	Switch(Box<dyn State>),
+	Exit,
+}
+
+pub trait State {
+	fn update(&mut self, context: &UpdateContext) -> std::io::Result<NextUpdateAction>;
+	fn render_frame(&self, frame: &mut Frame);
+
+	/// Снимок для трансляции зрителям; `None`, если сейчас не идёт игра
+	fn spectator_frame(&self) -> Option<SpectatorFrame> {
+		None
+	}
+}
@@ -0,0 +1,102 @@
+use std::fs;
+use std::io::{self, ErrorKind};
+use std::path::PathBuf;
+use std::env;
+
+use serde::{Serialize, de::DeserializeOwned};
+
+const APP_DIR_NAME: &str = "tetris-rust";
+
+/// Папка для данных игры (рекорды и т.п.). Можно переопределить через `TETRIS_DATA_DIR`.
+pub fn data_dir() -> PathBuf {
+	if let Some(dir) = env::var_os("TETRIS_DATA_DIR") {
+		return PathBuf::from(dir);
+	}
+
+	let base = env::var_os("XDG_DATA_HOME").map(PathBuf::from)
+		.or_else(|| env::var_os("APPDATA").map(PathBuf::from))
+		.or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".local").join("share")));
+
+	match base {
+		Some(base) => base.join(APP_DIR_NAME),
+		None => PathBuf::from("."),
+	}
+}
+
+/// Читает JSON-файл из папки данных
+pub fn read_json<T: DeserializeOwned>(file_name: &str) -> io::Result<T> {
+	let text = fs::read_to_string(data_dir().join(file_name))?;
+	serde_json::from_str(&text).map_err(|error| io::Error::new(ErrorKind::InvalidData, error))
+}
+
+/// Загружает JSON-файл из папки данных. Если файла нет — возвращает значение по умолчанию.
+pub fn load_json<T: DeserializeOwned + Default>(file_name: &str) -> io::Result<T> {
+	match read_json(file_name) {
+		Err(error) if error.kind() == ErrorKind::NotFound => Ok(T::default()),
+		result => result,
+	}
+}
+
+/// Сохраняет значение в JSON-файл в папке данных (путь может содержать подпапки).
+/// Пишет во временный файл и переименовывает его, чтобы не оставить повреждённый файл при сбое.
+pub fn save_json<T: Serialize>(file_name: &str, value: &T) -> io::Result<()> {
+	let path = data_dir().join(file_name);
+	if let Some(dir) = path.parent() {
+		fs::create_dir_all(dir)?;
+	}
+
+	let text = serde_json::to_string_pretty(value)
+		.map_err(|error| io::Error::new(ErrorKind::InvalidData, error))?;
+
+	let temp_path = path.with_extension("tmp");
+	fs::write(&temp_path, text)?;
+	fs::rename(&temp_path, &path)
+}
+
+/// Имена JSON-файлов в подпапке данных в виде `подпапка/файл.json`, по алфавиту.
+/// Отсутствующая подпапка даёт пустой список.
+pub fn list_json_files(dir_name: &str) -> io::Result<Vec<String>> {
+	let entries = match fs::read_dir(data_dir().join(dir_name)) {
+		Ok(entries) => entries,
+		Err(error) if error.kind() == ErrorKind::NotFound => return Ok(Vec::new()),
+		Err(error) => return Err(error),
+	};
+
+	let mut names = Vec::new();
+	for entry in entries {
+		let file_name = entry?.file_name();
+		let file_name = file_name.to_string_lossy();
+		if file_name.ends_with(".json") {
+			names.push(format!("{dir_name}/{file_name}"));
+		}
+	}
+	names.sort();
+
+	Ok(names)
+}
+
+/// Есть ли файл в папке данных
+pub fn exists(file_name: &str) -> bool {
+	data_dir().join(file_name).is_file()
+}
+
+/// Удаляет файл из папки данных. Отсутствие файла ошибкой не считается.
+pub fn remove(file_name: &str) -> io::Result<()> {
+	match fs::remove_file(data_dir().join(file_name)) {
+		Err(error) if error.kind() == ErrorKind::NotFound => Ok(()),
+		result => result,
+	}
+}
+
+/// Для проверок: папка данных во временной папке, своя у каждого запуска тестов,
+/// чтобы не трогать настоящие рекорды и сохранения
+#[cfg(test)]
+pub fn use_temp_data_dir() {
+	static INIT: std::sync::Once = std::sync::Once::new();
+	INIT.call_once(|| {
+		let dir = env::temp_dir().join(format!("{APP_DIR_NAME}-test-{}", std::process::id()));
+		// SAFETY: переменную задаёт только эта функция, один раз за процесс; остальные
+		// проверки окружение не меняют
+		unsafe { env::set_var("TETRIS_DATA_DIR", dir) };
+	});
+}
@@ -0,0 +1,442 @@
+use std::collections::VecDeque;
+use std::io::{self, BufRead, BufReader, Write};
+use std::process::{Child, Command, Stdio};
+use std::sync::mpsc::{self, Receiver, Sender, TryRecvError};
+use std::thread;
+use std::time::Duration;
+
+use serde::{Serialize, Deserialize};
+
+use crate::shared::*;
+use crate::input::PlayerAction;
+use crate::board::{ASCII_EMPTY_CELL, ASCII_FILLED_CELL, Board};
+use crate::figure::{Figure, FigureKind};
+use crate::game::{BOARD_SIZE, GameState};
+use crate::movegen::{self, Placement};
+use crate::bot::{Bot, BotPlayer, Weights};
+
+/// Высота доски в протоколе; строки выше нашей доски всегда пустые
+const TBP_BOARD_HEIGHT: usize = 40;
+/// Чем занятые клетки помечаются в отправляемой доске
+const TBP_FILLED_CELL: &str = "G";
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Orientation {
+	North,
+	East,
+	South,
+	West,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Spin {
+	#[default]
+	None,
+	Mini,
+	Full,
+}
+
+/// Положение фигуры: центр по SRS, `y` отсчитывается снизу
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Location {
+	#[serde(rename = "type")]
+	pub kind: FigureKind,
+	pub orientation: Orientation,
+	pub x: i32,
+	pub y: i32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct TbpMove {
+	pub location: Location,
+	#[serde(default)]
+	pub spin: Spin,
+}
+
+/// Сообщения от игры боту по Tetris Bot Protocol: JSON, по одному в строке,
+/// через stdin/stdout дочернего процесса бота
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum FrontendMessage {
+	Rules {},
+	Start {
+		hold: Option<FigureKind>,
+		queue: Vec<FigureKind>,
+		combo: u32,
+		back_to_back: bool,
+		/// Строки снизу вверх, `null` — пустая клетка
+		board: Vec<Vec<Option<String>>>,
+	},
+	Suggest,
+	Play {
+		#[serde(rename = "move")]
+		tbp_move: TbpMove,
+	},
+	NewPiece { piece: FigureKind },
+	Stop,
+	Quit,
+}
+
+/// Сообщения от бота игре
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum BotMessage {
+	Info {
+		name: String,
+		version: String,
+		author: String,
+		#[serde(default)]
+		features: Vec<String>,
+	},
+	Ready,
+	Error { reason: String },
+	Suggestion { moves: Vec<TbpMove> },
+}
+
+/// Клетки фигуры относительно центра в положении North, `y` вверх
+fn north_offsets(kind: FigureKind) -> [(i32, i32); 4] {
+	match kind {
+		FigureKind::I => [(-1, 0), (0, 0), (1, 0), (2, 0)],
+		FigureKind::O => [(0, 0), (1, 0), (0, 1), (1, 1)],
+		FigureKind::T => [(-1, 0), (0, 0), (1, 0), (0, 1)],
+		FigureKind::L => [(-1, 0), (0, 0), (1, 0), (1, 1)],
+		FigureKind::J => [(-1, 0), (0, 0), (1, 0), (-1, 1)],
+		FigureKind::S => [(-1, 0), (0, 0), (0, 1), (1, 1)],
+		FigureKind::Z => [(-1, 1), (0, 1), (0, 0), (1, 0)],
+	}
+}
+
+/// Клетки фигуры в координатах протокола (`y` снизу), отсортированные
+fn location_cells(location: &Location) -> Vec<(i32, i32)> {
+	let mut cells: Vec<(i32, i32)> = north_offsets(location.kind).iter()
+		.map(|&(x, y)| match location.orientation {
+			Orientation::North => (x, y),
+			Orientation::East => (y, -x),
+			Orientation::South => (-x, -y),
+			Orientation::West => (-y, x),
+		})
+		.map(|(x, y)| (location.x + x, location.y + y))
+		.collect();
+	cells.sort();
+	cells
+}
+
+/// Клетки нашей доски (`y` сверху) в координатах протокола, отсортированные
+fn to_tbp_cells(cells: &[Point], board_height: usize) -> Vec<(i32, i32)> {
+	let mut cells: Vec<(i32, i32)> = cells.iter()
+		.map(|cell| (cell.x as i32, (board_height - 1 - cell.y) as i32))
+		.collect();
+	cells.sort();
+	cells
+}
+
+/// Положение в протоколе для фигуры, стоящей в `position` на нашей доске
+pub fn location_for(figure: &Figure, position: Point, board_height: usize) -> Option<Location> {
+	let cells = to_tbp_cells(&movegen::figure_cells(figure, position), board_height);
+	[Orientation::North, Orientation::East, Orientation::South, Orientation::West].into_iter()
+		.flat_map(|orientation| cells.iter().map(move |&(x, y)| Location { kind: figure.kind, orientation, x, y }))
+		.find(|location| location_cells(location) == cells)
+}
+
+/// Достижимая из текущего положения постановка, занимающая те же клетки, что и ход бота
+fn find_placement(board: &Board, figure: &Figure, position: Point, tbp_move: &TbpMove) -> Option<Placement> {
+	let target = location_cells(&tbp_move.location);
+	let height = board.logical_size().height;
+	movegen::reachable_placements(board, figure, position)
+		.into_iter()
+		.find(|placement| to_tbp_cells(&placement.cells(), height) == target)
+}
+
+/// Доска в виде протокола: `TBP_BOARD_HEIGHT` строк снизу вверх
+fn board_to_tbp(board: &Board) -> Vec<Vec<Option<String>>> {
+	let Size { width, height } = board.logical_size();
+	(0..TBP_BOARD_HEIGHT)
+		.map(|row| (0..width)
+			.map(|x| {
+				let filled = row < height && board.is_blocked(x as isize, (height - 1 - row) as isize);
+				filled.then(|| String::from(TBP_FILLED_CELL))
+			})
+			.collect())
+		.collect()
+}
+
+/// Доска размера `BOARD_SIZE` из доски протокола; всё выше неё отбрасывается
+fn board_from_tbp(rows: &[Vec<Option<String>>]) -> Board {
+	let text: String = (0..BOARD_SIZE.height).rev()
+		.map(|row| {
+			let mut line: String = (0..BOARD_SIZE.width)
+				.map(|x| match rows.get(row).and_then(|cells| cells.get(x)) {
+					Some(Some(_)) => ASCII_FILLED_CELL,
+					_ => ASCII_EMPTY_CELL,
+				})
+				.collect();
+			line.push('\n');
+			line
+		})
+		.collect();
+	Board::from_ascii(&text).unwrap_or_else(|_| Board::new(BOARD_SIZE))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum TbpPhase {
+	/// Ждём `info`, затем `ready`
+	Handshake,
+	Ready,
+	/// Отправлен `suggest`, ждём `suggestion`
+	Thinking,
+}
+
+/// Внешний бот в дочернем процессе. На каждую фигуру отправляет ему `start` с
+/// текущей доской и очередью, просит подсказку, сообщает выбранный ход и
+/// останавливает — так мусор и любые изменения доски со стороны игры боту не
+/// нужно отслеживать.
+pub struct TbpBot {
+	child: Child,
+	/// Строки для потока записи в stdin бота: занятый бот не должен тормозить игру
+	outgoing: Sender<String>,
+	incoming: Receiver<BotMessage>,
+	phase: TbpPhase,
+	plan: VecDeque<PlayerAction>,
+	/// Для какой по счёту фигуры запрошена подсказка
+	planned_piece: Option<u32>,
+	action_interval: Duration,
+	since_last_action: Duration,
+	/// Играет вместо внешнего бота, если тот завершился или ответил ошибкой
+	fallback: Bot,
+	error: Option<String>,
+}
+
+impl TbpBot {
+	/// Запускает бота командой `command` (программа и аргументы через пробел)
+	pub fn spawn(command: &str) -> io::Result<Self> {
+		let mut parts = command.split_whitespace();
+		let program = parts.next()
+			.ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "пустая команда бота"))?;
+		let mut child = Command::new(program)
+			.args(parts)
+			.stdin(Stdio::piped())
+			.stdout(Stdio::piped())
+			.stderr(Stdio::null())
+			.spawn()?;
+
+		let mut stdin = child.stdin.take().expect("stdin задан как piped");
+		let stdout = child.stdout.take().expect("stdout задан как piped");
+		let (sender, incoming) = mpsc::channel();
+		// Непонятные строки пропускаются: протокол разрешает ботам новые сообщения
+		thread::spawn(move || {
+			for line in BufReader::new(stdout).lines() {
+				let Ok(line) = line else { break };
+				let Ok(message) = serde_json::from_str(&line) else { continue };
+				if sender.send(message).is_err() {
+					break;
+				}
+			}
+		});
+
+		let (outgoing, lines) = mpsc::channel::<String>();
+		// Завершается при ошибке записи — `send` узнаёт об этом по закрытому каналу
+		thread::spawn(move || {
+			for line in lines {
+				if stdin.write_all(line.as_bytes()).and_then(|_| stdin.flush()).is_err() {
+					break;
+				}
+			}
+		});
+
+		Ok(Self {
+			child,
+			outgoing,
+			incoming,
+			phase: TbpPhase::Handshake,
+			plan: VecDeque::new(),
+			planned_piece: None,
+			action_interval: Bot::DEFAULT_ACTION_INTERVAL,
+			since_last_action: Duration::ZERO,
+			fallback: Bot::new(),
+			error: None,
+		})
+	}
+
+	pub fn with_action_interval(mut self, action_interval: Duration) -> Self {
+		self.action_interval = action_interval;
+		self.fallback = std::mem::take(&mut self.fallback).with_action_interval(action_interval);
+		self
+	}
+
+	/// Почему внешний бот больше не играет
+	pub fn error(&self) -> Option<&str> {
+		self.error.as_deref()
+	}
+
+	/// Ставит сообщение в очередь потока записи, не дожидаясь, пока бот его прочтёт
+	fn send(&mut self, message: &FrontendMessage) {
+		let mut line = match serde_json::to_string(message) {
+			Ok(line) => line,
+			Err(error) => {
+				self.error = Some(format!("бот недоступен: {error}"));
+				return;
+			}
+		};
+		line.push('\n');
+		if self.outgoing.send(line).is_err() {
+			self.error = Some(String::from("бот недоступен: не удалось записать в stdin"));
+		}
+	}
+
+	fn handle_message(&mut self, game: &GameState, message: BotMessage) {
+		match (self.phase, message) {
+			(TbpPhase::Handshake, BotMessage::Info { .. }) => self.send(&FrontendMessage::Rules {}),
+			(TbpPhase::Handshake, BotMessage::Ready) => self.phase = TbpPhase::Ready,
+			(_, BotMessage::Error { reason }) => self.error = Some(format!("ошибка бота: {reason}")),
+			(TbpPhase::Thinking, BotMessage::Suggestion { moves }) => {
+				self.phase = TbpPhase::Ready;
+
+				// Фигура успела упасть, пока бот думал, — подсказка устарела
+				if self.planned_piece != Some(game.pieces_placed()) {
+					self.send(&FrontendMessage::Stop);
+					self.planned_piece = None;
+					return;
+				}
+				let chosen = moves.iter().find_map(|tbp_move| {
+					find_placement(game.board(), game.current_figure(), game.current_position(), tbp_move)
+						.map(|placement| (*tbp_move, placement))
+				});
+				self.plan = match chosen {
+					Some((tbp_move, placement)) => {
+						self.send(&FrontendMessage::Play { tbp_move });
+						placement.actions.into()
+					}
+					None => VecDeque::from([PlayerAction::Drop]),
+				};
+				self.send(&FrontendMessage::Stop);
+			}
+			_ => {}
+		}
+	}
+
+	fn request_suggestion(&mut self, game: &GameState) {
+		let queue = std::iter::once(game.current_figure().kind)
+			.chain(game.next_figure_kind())
+			.collect();
+		self.send(&FrontendMessage::Start {
+			hold: None,
+			queue,
+			combo: 0,
+			back_to_back: false,
+			board: board_to_tbp(game.board()),
+		});
+		self.send(&FrontendMessage::Suggest);
+		self.phase = TbpPhase::Thinking;
+		self.planned_piece = Some(game.pieces_placed());
+		self.plan.clear();
+	}
+}
+
+impl BotPlayer for TbpBot {
+	fn update(&mut self, game: &GameState, delta: Duration) -> Option<PlayerAction> {
+		if self.error.is_some() {
+			return self.fallback.update(game, delta);
+		}
+
+		loop {
+			match self.incoming.try_recv() {
+				Ok(message) => self.handle_message(game, message),
+				Err(TryRecvError::Empty) => break,
+				Err(TryRecvError::Disconnected) => {
+					self.error = Some(String::from("бот завершился"));
+					return None;
+				}
+			}
+		}
+
+		if game.is_paused() || game.is_game_over() {
+			return None;
+		}
+		if self.phase == TbpPhase::Ready && self.planned_piece != Some(game.pieces_placed()) {
+			self.request_suggestion(game);
+		}
+
+		self.since_last_action += delta;
+		if self.since_last_action < self.action_interval || self.plan.is_empty() {
+			return None;
+		}
+		self.since_last_action = Duration::ZERO;
+		self.plan.pop_front()
+	}
+
+	fn reset(&mut self) {
+		if self.phase == TbpPhase::Thinking {
+			self.send(&FrontendMessage::Stop);
+			self.phase = TbpPhase::Ready;
+		}
+		self.plan.clear();
+		self.planned_piece = None;
+		self.since_last_action = Duration::ZERO;
+		self.fallback.reset();
+	}
+}
+
+impl Drop for TbpBot {
+	fn drop(&mut self) {
+		self.send(&FrontendMessage::Quit);
+		let _ = self.child.kill();
+		let _ = self.child.wait();
+	}
+}
+
+/// Простой бот по протоколу для проверок: встроенная оценка доски
+/// (`bot::Weights`) вместо настоящего поиска. Читает stdin, пишет в stdout.
+pub fn run_mock_bot() -> io::Result<()> {
+	let weights = Weights::default();
+	let mut out = io::stdout().lock();
+	let mut reply = |message: &BotMessage| -> io::Result<()> {
+		writeln!(out, "{}", serde_json::to_string(message)?)?;
+		out.flush()
+	};
+
+	reply(&BotMessage::Info {
+		name: String::from("tetris-rust mock"),
+		version: String::from(env!("CARGO_PKG_VERSION")),
+		author: String::from("tetris-rust"),
+		features: Vec::new(),
+	})?;
+
+	let mut board = Board::new(BOARD_SIZE);
+	let mut queue: VecDeque<FigureKind> = VecDeque::new();
+	let spawn_position = Point::new(BOARD_SIZE.width / 2, 0);
+
+	for line in io::stdin().lock().lines() {
+		let Ok(message) = serde_json::from_str::<FrontendMessage>(&line?) else { continue };
+		match message {
+			FrontendMessage::Rules {} => reply(&BotMessage::Ready)?,
+			FrontendMessage::Start { queue: new_queue, board: rows, .. } => {
+				board = board_from_tbp(&rows);
+				queue = new_queue.into();
+			}
+			FrontendMessage::Suggest => {
+				let moves = queue.front()
+					.map(|kind| Figure::of_kind(*kind))
+					.and_then(|figure| weights.best_placement(&board, &figure, spawn_position))
+					.and_then(|placement| location_for(&placement.figure, placement.position, BOARD_SIZE.height))
+					.map(|location| TbpMove { location, spin: Spin::None })
+					.into_iter()
+					.collect();
+				reply(&BotMessage::Suggestion { moves })?;
+			}
+			FrontendMessage::Play { tbp_move } => {
+				if let Some(kind) = queue.pop_front() {
+					let placement = find_placement(&board, &Figure::of_kind(kind), spawn_position, &tbp_move);
+					if let Some(placement) = placement {
+						board.drop_figure(&placement.figure, &placement.position, Duration::ZERO);
+					}
+				}
+			}
+			FrontendMessage::NewPiece { piece } => queue.push_back(piece),
+			FrontendMessage::Stop => queue.clear(),
+			FrontendMessage::Quit => break,
+		}
+	}
+	Ok(())
+}
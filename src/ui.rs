@@ -0,0 +1,78 @@
+use itertools::{EitherOrBoth, Itertools};
+
+use crate::render::{Cell, Frame, Row, Style};
+
+pub type Pixel = [char; PIXEL_LENGTH];
+pub const PIXEL_LENGTH: usize = 2;
+
+pub trait PushPixel {
+	fn push_pixel(&mut self, pixel: Pixel);
+}
+impl PushPixel for String {
+	fn push_pixel(&mut self, pixel: Pixel) {
+		for ch in pixel {
+			self.push(ch);
+		}
+	}
+}
+/// Пиксель в оформлении по умолчанию
+impl PushPixel for Row {
+	fn push_pixel(&mut self, pixel: Pixel) {
+		self.push_styled_pixel(pixel, Style::default());
+	}
+}
+
+pub trait PushStyledPixel {
+	fn push_styled_pixel(&mut self, pixel: Pixel, style: Style);
+}
+impl PushStyledPixel for Row {
+	fn push_styled_pixel(&mut self, pixel: Pixel, style: Style) {
+		self.extend(pixel.map(|glyph| Cell::new(glyph, style)));
+	}
+}
+
+pub trait UIElement {
+	fn required_width(&self) -> usize;
+}
+
+impl UIElement for [String] {
+	fn required_width(&self) -> usize {
+		self.iter()
+			.map(|s| s.chars().count())
+			.max()
+			.unwrap_or(0)
+	}
+}
+
+/// Выводит части в кадр бок о бок, выравнивая каждую по её ширине
+pub fn push_parts_side_by_side(frame: &mut Frame, left: &Frame, right: &Frame, gap: usize) {
+	let left_width = left.width();
+	let right_width = right.width();
+
+	let blank = Cell::new(' ', Style::default());
+	let empty = Row::new();
+	for pair in left.rows().iter().zip_longest(right.rows()) {
+		use EitherOrBoth::*;
+
+		let (left_row, right_row) = match pair {
+			Both(left, right) => (left, right),
+			Left(left) => (left, &empty),
+			Right(right) => (&empty, right),
+		};
+
+		let mut row = left_row.clone();
+		row.resize(left_width + gap, blank);
+		row.extend_from_slice(right_row);
+		row.resize(left_width + gap + right_width, blank);
+		frame.push_row(row);
+	}
+}
+
+/// Выводит строки в кадр, дополняя их пробелами до общей ширины,
+/// чтобы перекрыть остатки предыдущего кадра
+pub fn push_lines(frame: &mut Frame, lines: &[String]) {
+	let width = lines.required_width();
+	for line in lines {
+		frame.push_line(format!("{:<width$}", line).as_str());
+	}
+}
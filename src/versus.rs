@@ -0,0 +1,295 @@
+use crossterm::event::KeyCode;
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+
+use crate::input::*;
+use crate::ui::*;
+use crate::state::*;
+use crate::render::Frame;
+use crate::mode::GameMode;
+use crate::game::{BOARD_SIZE, GameEvent, GameState, GarbageAttack};
+use crate::bot::{Bot, BotPlayer};
+use crate::menu::MenuState;
+
+/// Сколько строк мусора отправляет сопернику одна фиксация фигуры
+pub fn attack_lines(lines: u8, t_spin: bool, perfect_clear: bool) -> u8 {
+	if perfect_clear {
+		return 10;
+	}
+	match (lines, t_spin) {
+		(0, _) => 0,
+		(1, true) => 2,
+		(2, true) => 4,
+		(_, true) => 6,
+		(1, false) => 0,
+		(2, false) => 1,
+		(3, false) => 2,
+		(_, false) => 4,
+	}
+}
+
+/// Поток генератора дырок в мусоре; фигуры берутся из нулевого потока того же зерна
+const GARBAGE_RNG_STREAM: u64 = 1;
+
+/// Игра вдвоём за одной клавиатурой: две доски рядом, мусор за убранные линии
+pub struct VersusState {
+	games: [GameState; 2],
+	/// Отдельный генератор для дырок в мусоре, чтобы не сбивать очередь фигур
+	rng: ChaCha8Rng,
+	is_paused: bool,
+	/// Бот вместо второго игрока; тогда первому доступна вся раскладка
+	bot: Option<Box<dyn BotPlayer>>,
+}
+impl VersusState {
+	const PLAYER_KEYS: [PlayerKeys; 2] = [PlayerKeys::Left, PlayerKeys::Right];
+
+	/// Оба игрока получают одинаковую последовательность фигур
+	pub fn new(seed: u64) -> Self {
+		// Тот же ключ, но другой поток: дырки не повторяют очередь фигур
+		let mut rng = ChaCha8Rng::seed_from_u64(seed);
+		rng.set_stream(GARBAGE_RNG_STREAM);
+		Self {
+			games: [
+				GameState::new(GameMode::Versus, 0, seed),
+				GameState::new(GameMode::Versus, 0, seed),
+			],
+			rng,
+			is_paused: false,
+			bot: None,
+		}
+	}
+
+	/// Игра против встроенного бота: он занимает место второго игрока
+	pub fn against_bot(seed: u64) -> Self {
+		Self::new(seed).with_bot(Box::new(Bot::new()))
+	}
+
+	pub fn with_bot(mut self, bot: Box<dyn BotPlayer>) -> Self {
+		self.bot = Some(bot);
+		self
+	}
+
+	/// Новая партия с теми же участниками
+	fn rematch(&mut self) {
+		let mut bot = self.bot.take();
+		if let Some(bot) = &mut bot {
+			bot.reset();
+		}
+		*self = Self { bot, ..Self::new(rand::random()) };
+	}
+
+	fn is_finished(&self) -> bool {
+		self.games.iter().any(GameState::is_game_over)
+	}
+
+	/// Номер победившего игрока (с нуля); `None`, если оба проиграли одновременно
+	fn winner(&self) -> Option<usize> {
+		match (self.games[0].is_game_over(), self.games[1].is_game_over()) {
+			(false, true) => Some(0),
+			(true, false) => Some(1),
+			_ => None,
+		}
+	}
+
+	fn toggle_pause(&mut self) {
+		self.is_paused = !self.is_paused;
+		for game in &mut self.games {
+			game.toggle_pause();
+		}
+	}
+
+	/// Передаёт отправленный мусор сопернику
+	fn exchange_garbage(&mut self) {
+		for sender in 0..self.games.len() {
+			let mut lines = self.games[sender].take_outgoing_garbage();
+			while lines > 0 {
+				let chunk = lines.min(u8::MAX as u16) as u8;
+				lines -= chunk as u16;
+
+				let hole = self.rng.random_range(0..BOARD_SIZE.width) as u8;
+				let attack = GarbageAttack { lines: chunk, hole };
+				self.games[1 - sender].apply_event(GameEvent::Garbage(attack));
+			}
+		}
+	}
+}
+
+impl State for VersusState {
+	fn update(&mut self, context: &UpdateContext) -> std::io::Result<NextUpdateAction> {
+		for key_event in collect_last_key_events()? {
+			if key_event.is_release() {
+				continue;
+			}
+			if is_interrupt(&key_event) {
+				return Ok(NextUpdateAction::Exit);
+			}
+
+			if self.is_finished() {
+				match key_event.code {
+					KeyCode::Enter => self.rematch(),
+					KeyCode::Esc => {
+						return Ok(NextUpdateAction::Switch(Box::new(MenuState::new())));
+					}
+					_ => {}
+				}
+				continue;
+			}
+
+			// Выход и пауза общие для обоих игроков, поэтому обрабатываются один раз
+			match PlayerKeys::Left.action_for(key_event) {
+				PlayerAction::Exit => return Ok(NextUpdateAction::Switch(Box::new(MenuState::new()))),
+				PlayerAction::TogglePause => {
+					self.toggle_pause();
+					continue;
+				}
+				_ => {}
+			}
+
+			if self.bot.is_some() {
+				match PlayerAction::from_key_event(key_event) {
+					PlayerAction::DoNothing | PlayerAction::Restart => {}
+					action => self.games[0].apply_event(GameEvent::Action(action)),
+				}
+				continue;
+			}
+			for (game, keys) in self.games.iter_mut().zip(Self::PLAYER_KEYS) {
+				match keys.action_for(key_event) {
+					PlayerAction::DoNothing => {}
+					action => game.apply_event(GameEvent::Action(action)),
+				}
+			}
+		}
+
+		if let Some(bot) = &mut self.bot && let Some(action) = bot.update(&self.games[1], context.delta_time) {
+			self.games[1].apply_event(GameEvent::Action(action));
+		}
+
+		if !self.is_finished() {
+			for game in &mut self.games {
+				game.advance_time(context.delta_time);
+				if game.is_gravity_due() {
+					game.apply_event(GameEvent::Gravity);
+				}
+			}
+			self.exchange_garbage();
+		}
+
+		Ok(NextUpdateAction::Continue)
+	}
+
+	fn render_frame(&self, frame: &mut Frame) {
+		const GAP_BETWEEN_PARTS: usize = 4;
+
+		let parts: Vec<Frame> = self.games.iter()
+			.enumerate()
+			.map(|(index, game)| {
+				let mut game_frame = Frame::new();
+				game.render_frame(&mut game_frame);
+
+				let title = match (index, &self.bot) {
+					(1, Some(_)) => String::from("КОМПЬЮТЕР"),
+					_ => format!("ИГРОК {}", index + 1),
+				};
+				let mut part = Frame::from_lines(&[title, String::new()]);
+				part.append(&game_frame);
+				part
+			})
+			.collect();
+		push_parts_side_by_side(frame, &parts[0], &parts[1], GAP_BETWEEN_PARTS);
+
+		let mut footer = vec![String::new()];
+		if self.is_finished() {
+			footer.push(match (self.winner(), &self.bot) {
+				(Some(0), Some(_)) => String::from("ВЫ ПОБЕДИЛИ"),
+				(Some(_), Some(_)) => String::from("ПОБЕДИЛ КОМПЬЮТЕР"),
+				(Some(index), None) => format!("ПОБЕДИЛ ИГРОК {}", index + 1),
+				(None, _) => String::from("НИЧЬЯ"),
+			});
+			footer.push(String::from("Enter — реванш, Esc — в меню"));
+		} else if self.is_paused {
+			footer.push(String::from("ПАУЗА — P, чтобы продолжить"));
+		} else if self.bot.is_none() {
+			footer.push(String::from("ИГРОК 1: A/D/S, Q/W/E, пробел   ИГРОК 2: стрелки, `.`, Enter"));
+			footer.push(String::from("P — пауза, Esc — в меню"));
+		} else {
+			footer.push(String::from("P — пауза, Esc — в меню"));
+		}
+		push_lines(frame, &footer);
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::board::Board;
+	use crate::figure::FigureKind;
+	use crate::input::PlayerAction;
+
+	#[test]
+	fn attack_table() {
+		let normal: Vec<u8> = (0..=4).map(|lines| attack_lines(lines, false, false)).collect();
+		assert_eq!(normal, [0, 0, 1, 2, 4]);
+		let t_spin: Vec<u8> = (0..=3).map(|lines| attack_lines(lines, true, false)).collect();
+		assert_eq!(t_spin, [0, 2, 4, 6]);
+		assert_eq!(attack_lines(1, false, true), 10);
+		assert_eq!(attack_lines(4, true, true), 10);
+	}
+
+	/// Партия, где обоим первой выпадает палка; доски задаются отдельно
+	fn versus_with_boards(boards: [&Board; 2]) -> VersusState {
+		let seed = (0..)
+			.find(|seed| GameState::new(GameMode::Versus, 0, *seed).current_figure().kind == FigureKind::I)
+			.unwrap();
+		let mut versus = VersusState::new(seed);
+		for (game, board) in versus.games.iter_mut().zip(boards) {
+			*game = GameState::new(GameMode::Versus, 0, seed).with_board(board.clone());
+		}
+		versus
+	}
+
+	/// Колодец справа под тетрис; клетка сверху, чтобы он не очищал поле
+	fn tetris_ready_board() -> Board {
+		let mut text = String::from("..........\n").repeat(15);
+		text.push_str("#.........\n");
+		text.push_str(&"#########.\n".repeat(4));
+		Board::from_ascii(&text).unwrap()
+	}
+
+	fn drop_into_well(game: &mut GameState) {
+		for action in [PlayerAction::MoveRight; 4].into_iter().chain([PlayerAction::Drop]) {
+			game.apply_event(GameEvent::Action(action));
+		}
+	}
+
+	#[test]
+	fn sends_garbage_to_opponent() {
+		let empty = Board::new(BOARD_SIZE);
+		let mut versus = versus_with_boards([&tetris_ready_board(), &empty]);
+
+		drop_into_well(&mut versus.games[0]);
+		versus.exchange_garbage();
+		assert_eq!(versus.games[1].board().garbage_height(), 0);
+
+		// Мусор поднимается, когда соперник ставит фигуру, не убрав линий
+		versus.games[1].apply_event(GameEvent::Action(PlayerAction::Drop));
+		let board = versus.games[1].board();
+		assert_eq!(board.garbage_height(), 4);
+		let text = board.to_ascii();
+		let garbage_rows: Vec<&str> = text.lines().rev().take(4).collect();
+		assert!(garbage_rows.iter().all(|row| row.matches('.').count() == 1), "{text}");
+	}
+
+	#[test]
+	fn attack_cancels_pending_garbage() {
+		let mut versus = versus_with_boards([&tetris_ready_board(), &tetris_ready_board()]);
+
+		drop_into_well(&mut versus.games[0]);
+		versus.exchange_garbage();
+		// Ответный тетрис гасит весь ожидающий мусор, сопернику ничего не уходит
+		drop_into_well(&mut versus.games[1]);
+		assert_eq!(versus.games[1].take_outgoing_garbage(), 0);
+
+		versus.games[1].apply_event(GameEvent::Action(PlayerAction::Drop));
+		assert_eq!(versus.games[1].board().garbage_height(), 0);
+	}
+}
@@ -0,0 +1,67 @@
+use std::fs;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use tetris_rust::bot::{BotPlayer, Weights};
+use tetris_rust::game::{GameEvent, GameState};
+use tetris_rust::mode::GameMode;
+use tetris_rust::movegen;
+use tetris_rust::tbp::TbpBot;
+
+/// Пока бот по протоколу ставит три фигуры, встроенный бот-заглушка запущен
+/// дочерним процессом, а всё, что ему пишет игра, копируется в журнал
+#[cfg(unix)]
+#[test]
+fn plays_with_mock_bot_process() {
+	let dir = format!("{}/tbp-mock", env!("CARGO_TARGET_TMPDIR"));
+	fs::create_dir_all(&dir).unwrap();
+	let log = format!("{dir}/frontend.log");
+	let script = format!("{dir}/bot.sh");
+	fs::write(&script, format!("tee {log} | {} --tbp-mock-bot\n", env!("CARGO_BIN_EXE_tetris-rust"))).unwrap();
+
+	let mut game = GameState::new(GameMode::Marathon, 0, 7);
+	let mut bot = TbpBot::spawn(&format!("sh {script}")).unwrap()
+		.with_action_interval(Duration::ZERO);
+	let weights = Weights::default();
+	let deadline = Instant::now() + Duration::from_secs(10);
+
+	for _ in 0..3 {
+		// Заглушка выбирает ход той же оценкой, что и встроенный бот
+		let expected = weights.best_placement(game.board(), game.current_figure(), game.current_position())
+			.unwrap()
+			.cells();
+		let piece = game.pieces_placed();
+		while game.pieces_placed() == piece {
+			assert!(Instant::now() < deadline, "бот не поставил фигуру");
+			let figure = game.current_figure().clone();
+			let landing = game.board().drop_position(&figure, &game.current_position());
+			match bot.update(&game, Duration::ZERO) {
+				Some(action) => game.apply_event(GameEvent::Action(action)),
+				None => thread::sleep(Duration::from_millis(1)),
+			}
+			if game.pieces_placed() != piece {
+				assert_eq!(movegen::figure_cells(&figure, landing), expected);
+			}
+		}
+		assert_eq!(bot.error(), None);
+	}
+	drop(bot);
+
+	let expected: Vec<&str> = std::iter::once("rules")
+		.chain(["start", "suggest", "play", "stop"].repeat(3))
+		.collect();
+	let messages = loop {
+		let messages: Vec<String> = fs::read_to_string(&log).unwrap_or_default()
+			.lines()
+			.filter_map(|line| serde_json::from_str::<serde_json::Value>(line).ok())
+			.map(|message| message["type"].as_str().unwrap_or_default().to_owned())
+			.collect();
+		if messages.len() >= expected.len() || Instant::now() > deadline {
+			break messages;
+		}
+		thread::sleep(Duration::from_millis(5));
+	};
+	// После остановки может успеть дойти и `quit`
+	let received: Vec<&str> = messages.iter().take(expected.len()).map(String::as_str).collect();
+	assert_eq!(received, expected);
+}